@@ -12,9 +12,15 @@ CREATE TABLE IF NOT EXISTS study_sessions
 
   length INTEGER NOT NULL,
   video_length INTEGER NOT NULL CHECK(video_length <= length),
+  break_length INTEGER NOT NULL DEFAULT(0) CHECK(break_length <= length),
 
+  -- NULL for sessions saved before start times were recorded.
+  started INTEGER NULL,
   ended INTEGER NOT NULL DEFAULT(UNIXEPOCH()),
 
+  -- 'voice' for sessions tracked in voice channels, 'manual' for logged sessions approved by a moderator.
+  source TEXT NOT NULL DEFAULT('voice') CHECK(source IN ('voice', 'manual')),
+
   FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
   FOREIGN KEY (coin_reward_id) REFERENCES coin_transactions (id) ON DELETE SET NULL
 );
@@ -107,11 +113,15 @@ CREATE TABLE IF NOT EXISTS boosters
 (
   id INTEGER PRIMARY KEY,
   user_id INTEGER NOT NULL,
+  -- The reward the booster came from.
+  reward_id INTEGER NULL,
   -- Booster multiplier in percentage. 150 for 1.5x booster.
   multiplier INT NOT NULL,
+  activated INTEGER NOT NULL DEFAULT(UNIXEPOCH()),
   expiration INTEGER NOT NULL,
 
-  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+  FOREIGN KEY (reward_id) REFERENCES rewards (id) ON DELETE SET NULL
 );
 
 CREATE TABLE IF NOT EXISTS video_rewards_time_left
@@ -121,3 +131,185 @@ CREATE TABLE IF NOT EXISTS video_rewards_time_left
 
   FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
 );
+
+CREATE TABLE IF NOT EXISTS active_study_sessions
+(
+  user_id INTEGER NOT NULL UNIQUE,
+
+  started INTEGER NOT NULL,
+  video_start INTEGER NULL,
+  video_sum INTEGER NOT NULL,
+  break_start INTEGER NULL,
+  break_sum INTEGER NOT NULL,
+  -- Whether the ongoing break was started by a Pomodoro cycle, which ends it.
+  pomodoro_break INTEGER NOT NULL DEFAULT(0),
+
+  -- When the user left voice, if they may still rejoin within the reconnect grace window.
+  left_at INTEGER NULL,
+
+  -- Last time the session was known to be active.
+  last_seen INTEGER NOT NULL DEFAULT(UNIXEPOCH()),
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Breaks that have ended in the active study sessions.
+CREATE TABLE IF NOT EXISTS active_study_breaks
+(
+  user_id INTEGER NOT NULL,
+
+  started INTEGER NOT NULL,
+  ended INTEGER NOT NULL,
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Times the users were away from voice during the active study sessions, having rejoined within the reconnect grace window.
+CREATE TABLE IF NOT EXISTS active_study_absences
+(
+  user_id INTEGER NOT NULL,
+
+  started INTEGER NOT NULL,
+  ended INTEGER NOT NULL,
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS leaderboard_archive
+(
+  user_id INTEGER NOT NULL,
+  -- Month of the leaderboard, e.g. 2024-11.
+  month TEXT NOT NULL,
+  place INTEGER NOT NULL,
+  study_amount INTEGER NOT NULL,
+
+  PRIMARY KEY (month, place),
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Users that want to know when someone passes them on the leaderboard.
+CREATE TABLE IF NOT EXISTS rank_change_notifications
+(
+  user_id INTEGER NOT NULL UNIQUE,
+  -- Last time the user was notified, for rate limiting.
+  last_notified INTEGER NULL,
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Top rank roles currently given to users by the bot.
+CREATE TABLE IF NOT EXISTS top_role_holders
+(
+  role_id INTEGER NOT NULL,
+  user_id INTEGER NOT NULL,
+
+  PRIMARY KEY (role_id, user_id),
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS user_timezones
+(
+  user_id INTEGER NOT NULL UNIQUE,
+  -- IANA timezone name, like Europe/Stockholm.
+  timezone TEXT NOT NULL,
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS inventory
+(
+  user_id INTEGER NOT NULL,
+  -- Kind of item, see inventory::Item.
+  item INTEGER NOT NULL,
+  amount INTEGER NOT NULL CHECK(amount >= 0),
+
+  PRIMARY KEY (user_id, item),
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Days on which a streak freeze kept the user's streak alive.
+CREATE TABLE IF NOT EXISTS frozen_streak_days
+(
+  user_id INTEGER NOT NULL,
+  -- Day in the user's timezone, e.g. 2024-11-05.
+  day TEXT NOT NULL,
+
+  PRIMARY KEY (user_id, day),
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Subject a study session was about.
+CREATE TABLE IF NOT EXISTS session_tags
+(
+  session_id INTEGER NOT NULL UNIQUE,
+  subject TEXT NOT NULL,
+
+  FOREIGN KEY (session_id) REFERENCES study_sessions (id) ON DELETE CASCADE
+);
+
+-- Subject given to the user's sessions when they end.
+CREATE TABLE IF NOT EXISTS subject_presets
+(
+  user_id INTEGER NOT NULL UNIQUE,
+  subject TEXT NOT NULL,
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- What the user wrote they accomplished in a study session.
+CREATE TABLE IF NOT EXISTS session_notes
+(
+  session_id INTEGER NOT NULL UNIQUE,
+  note TEXT NOT NULL,
+  written INTEGER NOT NULL DEFAULT(UNIXEPOCH()),
+
+  FOREIGN KEY (session_id) REFERENCES study_sessions (id) ON DELETE CASCADE
+);
+
+-- Study time the user aims for each day or week.
+CREATE TABLE IF NOT EXISTS study_goals
+(
+  user_id INTEGER NOT NULL,
+  -- 0 = daily, 1 = weekly.
+  period INTEGER NOT NULL,
+  minutes INTEGER NOT NULL CHECK(minutes > 0),
+
+  PRIMARY KEY (user_id, period),
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Periods in which the user met their goal.
+CREATE TABLE IF NOT EXISTS completed_goals
+(
+  user_id INTEGER NOT NULL,
+  period INTEGER NOT NULL,
+  -- Start of the day or week, in the user's timezone.
+  period_start INTEGER NOT NULL,
+  -- The goal at the time it was met.
+  minutes INTEGER NOT NULL,
+  completed INTEGER NOT NULL DEFAULT(UNIXEPOCH()),
+
+  PRIMARY KEY (user_id, period, period_start),
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Manually logged sessions waiting for moderator review.
+CREATE TABLE IF NOT EXISTS session_submissions
+(
+  id INTEGER PRIMARY KEY,
+  user_id INTEGER NOT NULL,
+
+  started INTEGER NOT NULL,
+  length INTEGER NOT NULL CHECK(length > 0),
+  note TEXT NULL,
+
+  submitted INTEGER NOT NULL DEFAULT(UNIXEPOCH()),
+
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use sqlx::types::time::OffsetDateTime;
+
+use crate::prelude::{elapsed_between, ActOnUser};
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BoosterStacking {
+    /// 1.5x and 2x boosters make 3x.
+    Multiply,
+    /// 1.5x and 2x boosters make 2.5x.
+    Add,
+    /// 1.5x and 2x boosters make 2x.
+    #[default]
+    Max
+}
+
+impl BoosterStacking {
+    /// Combines the multipliers (in percentage) of simultaneously active boosters into one factor.
+    fn combine(self, multipliers: impl Iterator<Item = u16>) -> f64 {
+        let factors = multipliers.map(|m| m as f64 / 100.0);
+        match self {
+            BoosterStacking::Multiply => factors.product(),
+            BoosterStacking::Add => 1.0 + factors.map(|f| f - 1.0).sum::<f64>(),
+            BoosterStacking::Max => factors.fold(1.0, f64::max)
+        }
+    }
+}
+
+pub struct Booster {
+    /// Multiplier in percentage. 150 for 1.5x booster.
+    pub multiplier: u16,
+    pub activated: OffsetDateTime,
+    pub expiration: OffsetDateTime
+}
+
+impl Booster {
+    /// How long the booster was active between start and end, outside of breaks.
+    pub fn active_during(&self, start: OffsetDateTime, end: OffsetDateTime, breaks: &[(OffsetDateTime, OffsetDateTime)]) -> Duration {
+        studied_between(self.activated.max(start), self.expiration.min(end), breaks)
+    }
+}
+
+/// Time between start and end that is not in any of the breaks, which do not overlap.
+fn studied_between(start: OffsetDateTime, end: OffsetDateTime, breaks: &[(OffsetDateTime, OffsetDateTime)]) -> Duration {
+    let on_break = breaks
+        .iter()
+        .map(|&(break_start, break_end)| elapsed_between(break_start.max(start), break_end.min(end)))
+        .sum::<Duration>();
+
+    elapsed_between(start, end).saturating_sub(on_break)
+}
+
+/// Boosters that were active at some point between start and end.
+pub async fn user_boosters(ctx: &ActOnUser<'_>, start: OffsetDateTime, end: OffsetDateTime) -> Vec<Booster> {
+    let uid = ctx.uid();
+    let start = start.unix_timestamp();
+    let end = end.unix_timestamp();
+
+    sqlx::query!("
+    SELECT multiplier, activated, expiration
+    FROM boosters
+    JOIN users ON user_id = users.id
+    WHERE
+        uid = $1 AND
+        activated < $3 AND
+        expiration > $2
+    ORDER BY activated
+    ", uid, start, end)
+        .fetch_all(ctx.0)
+        .await.unwrap()
+        .into_iter()
+        .map(|r| Booster {
+            multiplier: r.multiplier as u16,
+            activated: OffsetDateTime::from_unix_timestamp(r.activated).unwrap(),
+            expiration: OffsetDateTime::from_unix_timestamp(r.expiration).unwrap()
+        })
+        .collect()
+}
+
+/// Applies boosters to coins earned evenly between start and end, outside of breaks.
+/// Boosters only count for the studied part of the period they were active in.
+pub fn apply_boosters(coins: u64, boosters: &[Booster], stacking: BoosterStacking, start: OffsetDateTime, end: OffsetDateTime, breaks: &[(OffsetDateTime, OffsetDateTime)]) -> u64 {
+    let total = studied_between(start, end, breaks);
+    if boosters.is_empty() || total.is_zero() {
+        return coins
+    }
+
+    // Points in time where the set of active boosters changes.
+    let mut points = boosters
+        .iter()
+        .flat_map(|b| [b.activated, b.expiration])
+        .filter(|&t| start < t && t < end)
+        .chain([start, end])
+        .collect::<Vec<_>>();
+    points.sort();
+    points.dedup();
+
+    let boosted_secs = points
+        .windows(2)
+        .map(|w| {
+            let multiplier = stacking.combine(boosters
+                .iter()
+                .filter(|b| b.activated <= w[0] && b.expiration >= w[1])
+                .map(|b| b.multiplier));
+
+            studied_between(w[0], w[1], breaks).as_secs_f64() * multiplier
+        })
+        .sum::<f64>();
+
+    (coins as f64 * boosted_secs / total.as_secs_f64()).round() as u64
+}
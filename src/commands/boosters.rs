@@ -0,0 +1,75 @@
+use poise::serenity_prelude::MessageBuilder;
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{Context, Error};
+
+/// View your active boosters.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn boosters(
+    ctx: Context<'_>,
+    #[description = "Also show expired boosters"]
+    history: Option<bool>
+) -> Result<(), Error> {
+    let uid = i64::from(ctx.author().id);
+
+    let boosters = sqlx::query!(r#"
+    SELECT
+        multiplier,
+        expiration,
+        rewards.reason AS "reason?",
+        rewards.received AS "received?"
+    FROM boosters
+    JOIN users ON boosters.user_id = users.id
+    LEFT JOIN rewards ON reward_id = rewards.id
+    WHERE uid = $1
+    ORDER BY expiration DESC
+    LIMIT 25
+    "#, uid)
+        .fetch_all(&ctx.data().db_pool)
+        .await?;
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+
+    let (active, expired): (Vec<_>, Vec<_>) = boosters
+        .iter()
+        .partition(|r| r.expiration > now);
+
+    let mut b = MessageBuilder::new();
+
+    if active.is_empty() {
+        b.push_line("You have no active boosters.");
+    }
+
+    for booster in active.iter().rev() {
+        b.push(":zap: ");
+        b.push_bold(format!("{}x", booster.multiplier as f64 / 100.0));
+        b.push(format!(" booster, expires <t:{}:R>", booster.expiration));
+        if let (Some(reason), Some(received)) = (&booster.reason, booster.received) {
+            b.push(format!(" (*{}* <t:{}:d>)", reason, received));
+        }
+        b.push_line("");
+    }
+
+    if history.unwrap_or(false) {
+        b.push_line("");
+        b.push_line("**Expired boosters**");
+
+        if expired.is_empty() {
+            b.push_line("-# None yet.");
+        }
+
+        for booster in expired {
+            b.push("-# ");
+            b.push_bold(format!("{}x", booster.multiplier as f64 / 100.0));
+            b.push(format!(" booster, expired <t:{}:R>", booster.expiration));
+            if let (Some(reason), Some(received)) = (&booster.reason, booster.received) {
+                b.push(format!(" (*{}* <t:{}:d>)", reason, received));
+            }
+            b.push_line("");
+        }
+    }
+
+    ctx.reply(b.build()).await?;
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use crate::{inventory::{give_item, user_item_amount, Item}, prelude::{take_coins, ActOnUser}, Context, Error};
+
+/// Buy streak freezes, which keep your streak alive if you miss a day.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn buy_freeze(
+    ctx: Context<'_>,
+    #[description = "How many to buy (one if empty)"]
+    #[min = 1]
+    #[max = 100]
+    amount: Option<u64>
+) -> Result<(), Error> {
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+    let amount = amount.unwrap_or(1);
+
+    let Some(cost) = ctx.data().config.streak_freeze_cost else {
+        return Err(Error::from("Streak freezes are not for sale."))
+    };
+    let cost = cost
+        .checked_mul(amount)
+        .ok_or(Error::from("That is too many streak freezes."))?;
+
+    take_coins(
+        act_on_user_ctx,
+        cost,
+        "streak freezes",
+        None).await?;
+
+    give_item(act_on_user_ctx, Item::StreakFreeze, amount).await;
+
+    ctx.reply(format!(
+        ":ice_cube: Bought **{}** streak freeze{}. You now have **{}**.",
+        amount,
+        if amount == 1 { "" } else { "s" },
+        user_item_amount(act_on_user_ctx, Item::StreakFreeze).await)).await?;
+
+    Ok(())
+}
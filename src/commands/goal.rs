@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use humantime::format_duration;
+use poise::ChoiceParameter;
+
+use crate::{goals::{set_user_goal, GoalPeriod}, prelude::ActOnUser, Context, Error};
+
+/// Set how many hours you aim to study each day or week.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn goal(
+    ctx: Context<'_>,
+    #[description = "Daily or weekly goal"]
+    period: GoalPeriod,
+    #[description = "Hours to study (clears the goal if empty)"]
+    #[max = 168]
+    hours: Option<f64>
+) -> Result<(), Error> {
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+
+    let rules = &ctx.data().config.study_rules;
+    let min_goal = Duration::from_secs(rules.min_goal_minutes.max(rules.streak_min_session_minutes) * 60);
+
+    let goal = hours.map(|h| Duration::from_secs((h.max(0.0) * 60.0).round() as u64 * 60));
+
+    if goal.is_some_and(|g| g.is_zero() || g < min_goal) {
+        return Err(Error::from(format!("Your goal must be at least {}.",
+            format_duration(min_goal.max(Duration::from_secs(60))))))
+    }
+
+    set_user_goal(act_on_user_ctx, period, goal).await;
+
+    match goal {
+        Some(goal) => ctx.reply(format!(":dart: Your {} goal is now **{}**.",
+            period.name().to_lowercase(),
+            format_duration(goal))).await?,
+        None => ctx.reply(format!(":dart: Your {} goal has been cleared.",
+            period.name().to_lowercase())).await?
+    };
+
+    Ok(())
+}
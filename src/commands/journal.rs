@@ -0,0 +1,18 @@
+use poise::{serenity_prelude::CreateAllowedMentions, CreateReply};
+
+use crate::{journal::journal_page, prelude::ActOnUser, Context, Error};
+
+/// Page through the notes you wrote on your study sessions.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn journal(ctx: Context<'_>) -> Result<(), Error> {
+    let (content, components) = journal_page(
+        &ActOnUser(&ctx.data().db_pool, ctx.author().id),
+        0).await;
+
+    ctx.send(CreateReply::default()
+        .content(content)
+        .components(components)
+        .allowed_mentions(CreateAllowedMentions::new())).await?;
+
+    Ok(())
+}
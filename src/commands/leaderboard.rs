@@ -0,0 +1,54 @@
+use chrono::NaiveDate;
+use poise::{serenity_prelude::CreateAllowedMentions, CreateReply};
+
+use crate::{leaderboard::{leaderboard_page, LeaderboardMetric, LeaderboardPeriod}, prelude::ActOnUser, timezone::{from_naive, local_midnight, user_timezone}, Context, Error};
+
+/// View the study leaderboard.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "What to rank by (study time if empty)"]
+    metric: Option<LeaderboardMetric>,
+    #[description = "Period to rank (this month if empty)"]
+    period: Option<LeaderboardPeriod>,
+    #[description = "Custom range start date (YYYY-MM-DD), instead of a period"]
+    from: Option<String>,
+    #[description = "Custom range end date, inclusive (YYYY-MM-DD)"]
+    to: Option<String>
+) -> Result<(), Error> {
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+    let tz = user_timezone(act_on_user_ctx).await;
+
+    let (start, end) = match (period, from, to) {
+        (period, None, None) => period.unwrap_or(LeaderboardPeriod::Month).bounds_in(tz),
+        (None, Some(from), Some(to)) => {
+            let from = NaiveDate::parse_from_str(&from, "%Y-%m-%d")?;
+            let to = NaiveDate::parse_from_str(&to, "%Y-%m-%d")?;
+
+            if from > to {
+                return Err(Error::from("The start date must be before the end date."))
+            }
+
+            let midnight = |d: NaiveDate| local_midnight(from_naive(d), tz);
+
+            (midnight(from), midnight(to.succ_opt().unwrap()))
+        }
+        (Some(_), _, _) => return Err(Error::from("Choose either a period or a custom range, not both.")),
+        _ => return Err(Error::from("A custom range needs both a start and an end date."))
+    };
+
+    let (content, components) = leaderboard_page(
+        act_on_user_ctx,
+        &ctx.data().config.study_rules,
+        metric.unwrap_or(LeaderboardMetric::StudyTime),
+        start,
+        end,
+        0).await;
+
+    ctx.send(CreateReply::default()
+        .content(content)
+        .components(components)
+        .allowed_mentions(CreateAllowedMentions::new())).await?;
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use humantime::format_duration;
+use poise::{serenity_prelude::{CreateAllowedMentions, Mentionable, MessageBuilder, UserId}, CreateReply};
+
+use crate::{leaderboard::LeaderboardPeriod, rollover::archive_month, Context, Error};
+
+/// View the final leaderboard of a past month.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn leaderboard_archive(
+    ctx: Context<'_>,
+    #[description = "Month (YYYY-MM, last month if empty)"]
+    month: Option<String>
+) -> Result<(), Error> {
+    let month = month.unwrap_or_else(|| {
+        let this_month_start = LeaderboardPeriod::Month.bounds().0;
+        archive_month(this_month_start - time::Duration::days(1))
+    });
+
+    let uid = i64::from(ctx.author().id);
+
+    let places = sqlx::query!("
+    SELECT uid, place, study_amount
+    FROM leaderboard_archive
+    JOIN users ON user_id = users.id
+    WHERE month = $1 AND (place <= 10 OR uid = $2)
+    ORDER BY place
+    ", month, uid)
+        .fetch_all(&ctx.data().db_pool)
+        .await?;
+
+    if places.is_empty() {
+        return Err(Error::from(format!("There is no archived leaderboard for {}.", month)))
+    }
+
+    let mut b = MessageBuilder::new();
+    b.push_line(format!("## :trophy: Leaderboard of {}", month));
+
+    for r in places {
+        let line = format!("**{}.** {} - {}",
+            r.place,
+            UserId::new(r.uid as u64).mention(),
+            format_duration(Duration::from_secs(r.study_amount as u64 / 60 * 60)));
+
+        if r.uid == uid {
+            b.push_line(format!(":point_right: {}", line));
+        } else {
+            b.push_line(line);
+        }
+    }
+
+    ctx.send(CreateReply::default()
+        .content(b.build())
+        .allowed_mentions(CreateAllowedMentions::new())).await?;
+
+    Ok(())
+}
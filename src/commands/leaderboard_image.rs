@@ -0,0 +1,190 @@
+use std::{collections::HashMap, sync::Arc};
+
+use poise::{serenity_prelude::{CreateAttachment, User}, CreateReply};
+use resvg::usvg::ImageKind;
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{leaderboard::{fetch_leaderboard, LeaderboardMetric, LeaderboardPeriod}, prelude::ActOnUser, render::render_png, timezone::{local_date, user_timezone}, Context, Error};
+
+const WIDTH: u32 = 720;
+const HEADER_HEIGHT: u32 = 90;
+const ROW_HEIGHT: u32 = 64;
+const AVATAR_SIZE: u32 = 48;
+
+/// Escapes text for use in SVG.
+fn escape(text: &str) -> String {
+    text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn initials(name: &str) -> String {
+    name
+        .split_whitespace()
+        .take(2)
+        .flat_map(|word| word.chars().next())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// PNG avatar of the user from the Discord CDN.
+async fn fetch_avatar(ctx: Context<'_>, user: &User) -> Option<Vec<u8>> {
+    let url = match &user.avatar {
+        Some(hash) => format!("https://cdn.discordapp.com/avatars/{}/{}.png?size=64", user.id, hash),
+        None => user.default_avatar_url()
+    };
+
+    CreateAttachment::url(ctx.http(), &url).await
+        .ok()
+        .map(|a| a.data)
+}
+
+/// Marker showing how the place changed, drawn to the left of x.
+fn movement_marker(x: u32, y: u32, movement: Option<i64>) -> String {
+    match movement {
+        None => format!(
+            r##"<text x="{}" y="{}" font-size="12" font-weight="bold" fill="#4a90d9" text-anchor="middle">NEW</text>"##,
+            x, y + 4),
+        Some(0) => format!(
+            r##"<rect x="{}" y="{}" width="12" height="3" fill="#999999"/>"##,
+            x - 6, y - 1),
+        Some(m) if m > 0 => format!(
+            r##"<path d="M{} {} l6 -9 l6 9 z" fill="#3aa655"/><text x="{}" y="{}" font-size="11" fill="#3aa655" text-anchor="middle">{}</text>"##,
+            x - 6, y + 2, x, y + 15, m),
+        Some(m) => format!(
+            r##"<path d="M{} {} l6 9 l6 -9 z" fill="#d9534f"/><text x="{}" y="{}" font-size="11" fill="#d9534f" text-anchor="middle">{}</text>"##,
+            x - 6, y - 7, x, y + 15, -m)
+    }
+}
+
+/// Post a rendered image of the leaderboard.
+#[poise::command(slash_command, prefix_command)]
+pub async fn leaderboard_image(
+    ctx: Context<'_>,
+    #[description = "Period to rank (this month if empty)"]
+    period: Option<LeaderboardPeriod>
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let pool = &ctx.data().db_pool;
+    let rules = &ctx.data().config.study_rules;
+    let tz = user_timezone(&ActOnUser(pool, ctx.author().id)).await;
+    let (start, end) = period.unwrap_or(LeaderboardPeriod::Month).bounds_in(tz);
+
+    let places = fetch_leaderboard(pool, rules, LeaderboardMetric::StudyTime, start, end, Some(10), 0).await;
+
+    // Places as of a day ago, to show how they moved since.
+    let day_ago = (OffsetDateTime::now_utc() - time::Duration::days(1)).min(end);
+    let previous_places = if day_ago > start {
+        fetch_leaderboard(pool, rules, LeaderboardMetric::StudyTime, start, day_ago, None, 0).await
+    } else {
+        Vec::new()
+    };
+
+    let height = HEADER_HEIGHT + ROW_HEIGHT * places.len().max(1) as u32 + 20;
+
+    let mut svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">
+<rect width="{w}" height="{h}" fill="#1e1f22"/>
+<text x="30" y="48" font-size="30" font-weight="bold" fill="#ffffff">Leaderboard</text>
+<text x="30" y="74" font-size="15" fill="#b5bac1">{}</text>
+"##,
+        if start == OffsetDateTime::UNIX_EPOCH {
+            "All time".to_string()
+        } else {
+            format!("{} – {}", local_date(start, tz), local_date(end - time::Duration::SECOND, tz))
+        },
+        w = WIDTH,
+        h = height);
+
+    if places.is_empty() {
+        svg += &format!(
+            r##"<text x="30" y="{}" font-size="18" fill="#b5bac1">Nobody here yet.</text>"##,
+            HEADER_HEIGHT + 36);
+    }
+
+    let mut images = HashMap::new();
+
+    for (i, (user_id, study_amount)) in places.iter().enumerate() {
+        let y = HEADER_HEIGHT + ROW_HEIGHT * i as u32;
+        let center_y = y + ROW_HEIGHT / 2;
+
+        // Deleted accounts cannot be fetched, but still have a place.
+        let user = user_id.to_user(ctx).await.ok();
+        let name = user
+            .as_ref()
+            .map(|u| u.global_name.clone().unwrap_or(u.name.clone()))
+            .unwrap_or(user_id.to_string());
+
+        let background = match i {
+            0 => "#8a6d1d",
+            1 => "#6b6f75",
+            2 => "#7a4b26",
+            _ if i % 2 == 0 => "#2b2d31",
+            _ => "#232428"
+        };
+        svg += &format!(
+            r##"<rect x="15" y="{}" width="{}" height="{}" rx="10" fill="{}"/>"##,
+            y + 4, WIDTH - 30, ROW_HEIGHT - 8, background);
+
+        svg += &format!(
+            r##"<text x="50" y="{}" font-size="22" font-weight="bold" fill="#ffffff" text-anchor="middle">{}</text>"##,
+            center_y + 8, i + 1);
+
+        if !previous_places.is_empty() {
+            let movement = previous_places
+                .iter()
+                .position(|(previous_user_id, _)| previous_user_id == user_id)
+                .map(|previous| previous as i64 - i as i64);
+            svg += &movement_marker(95, center_y, movement);
+        }
+
+        let avatar_x = 125;
+        let avatar_y = center_y - AVATAR_SIZE / 2;
+        let radius = AVATAR_SIZE / 2;
+
+        let avatar = match &user {
+            Some(user) => fetch_avatar(ctx, user).await,
+            None => None
+        };
+
+        match avatar {
+            Some(avatar) => {
+                let href = format!("avatar{}", i);
+                images.insert(href.clone(), ImageKind::PNG(Arc::new(avatar)));
+                svg += &format!(
+                    r##"<clipPath id="clip{i}"><circle cx="{cx}" cy="{cy}" r="{r}"/></clipPath><image x="{x}" y="{y}" width="{s}" height="{s}" xlink:href="{href}" clip-path="url(#clip{i})"/>"##,
+                    i = i, cx = avatar_x + radius, cy = center_y, r = radius,
+                    x = avatar_x, y = avatar_y, s = AVATAR_SIZE, href = href);
+            }
+            None => {
+                svg += &format!(
+                    r##"<circle cx="{}" cy="{}" r="{}" fill="#5865f2"/><text x="{}" y="{}" font-size="18" font-weight="bold" fill="#ffffff" text-anchor="middle">{}</text>"##,
+                    avatar_x + radius, center_y, radius,
+                    avatar_x + radius, center_y + 6,
+                    escape(&user.as_ref().map(|_| initials(&name)).unwrap_or("?".to_string())));
+            }
+        }
+
+        svg += &format!(
+            r##"<text x="{}" y="{}" font-size="20" fill="#ffffff">{}</text>"##,
+            avatar_x + AVATAR_SIZE + 16, center_y + 7, escape(&name));
+
+        svg += &format!(
+            r##"<text x="{}" y="{}" font-size="20" font-weight="bold" fill="#ffffff" text-anchor="end">{:.1} h</text>"##,
+            WIDTH - 35, center_y + 7, *study_amount as f64 / 3600.0);
+    }
+
+    svg += "</svg>";
+
+    let png = render_png(&svg, images)?;
+
+    ctx.send(
+        CreateReply::default()
+        .attachment(CreateAttachment::bytes(png, "leaderboard.png"))
+    ).await?;
+
+    Ok(())
+}
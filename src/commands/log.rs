@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveTime};
+use humantime::parse_duration;
+use poise::serenity_prelude::ChannelId;
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{journal::MAX_NOTE_LENGTH, manual_sessions::{describe_time_ranges, overlapping_sessions, review_message, submit_session}, prelude::ActOnUser, timezone::{from_local, user_timezone}, Context, Error};
+
+/// Log a session you studied away from Discord, to be approved by a moderator.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn log(
+    ctx: Context<'_>,
+    #[description = "Day you started (YYYY-MM-DD)"]
+    date: String,
+    #[description = "Time you started, in your timezone (HH:MM)"]
+    start: String,
+    #[description = "How long you studied (e.g. \"1h 30m\")"]
+    length: String,
+    #[description = "What you accomplished"]
+    #[max_length = 1000]
+    note: Option<String>
+) -> Result<(), Error> {
+    let Some(log_review_channel) = ctx.data().config.channels.log_review_channel else {
+        return Err(Error::from("Logging sessions is not enabled."))
+    };
+
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+    let rules = &ctx.data().config.study_rules;
+    let tz = user_timezone(act_on_user_ctx).await;
+
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")?;
+    let start = NaiveTime::parse_from_str(&start, "%H:%M")?;
+    let length = parse_duration(&length)?;
+
+    let started = from_local(date.and_time(start), tz)
+        .ok_or(Error::from("That time does not exist in your timezone."))?;
+    let now = OffsetDateTime::now_utc();
+
+    if length < Duration::from_secs(60) || length > Duration::from_secs(24 * 60 * 60) {
+        return Err(Error::from("Sessions must be between a minute and a day long."))
+    }
+
+    if started + length > now {
+        return Err(Error::from("You can only log sessions that have ended."))
+    }
+
+    if started < now - time::Duration::days(rules.manual_session_max_age_days as i64) {
+        return Err(Error::from(format!("You can only log sessions from the last {} days.", rules.manual_session_max_age_days)))
+    }
+
+    let studying_since = ctx.data().study_states.lock().await.get(&ctx.author().id).map(|s| s.start);
+    let overlapping = overlapping_sessions(act_on_user_ctx, studying_since, started, started + length).await;
+    if !overlapping.is_empty() {
+        return Err(Error::from(format!("This overlaps with time you already studied or logged:\n{}", describe_time_ranges(&overlapping))))
+    }
+
+    let note = note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    if note.is_some_and(|n| n.chars().count() > MAX_NOTE_LENGTH) {
+        return Err(Error::from(format!("Notes can be at most {} characters long.", MAX_NOTE_LENGTH)))
+    }
+
+    let submission = submit_session(act_on_user_ctx, started, length, note).await;
+
+    ChannelId::new(log_review_channel)
+        .send_message(ctx, review_message(&submission))
+        .await?;
+
+    ctx.reply(":pencil: Your session was sent to the moderators for review. You will be notified when it is reviewed.").await?;
+
+    Ok(())
+}
@@ -4,5 +4,21 @@ pub mod stats;
 pub mod star;
 pub mod simulate_study_session;
 pub mod results;
+pub mod take_break;
+pub mod boosters;
+pub mod leaderboard;
+pub mod privacy;
+pub mod leaderboard_archive;
+pub mod leaderboard_image;
+pub mod notifications;
+pub mod timezone;
+pub mod buy_freeze;
+pub mod session;
+pub mod studying;
+pub mod pomodoro;
+pub mod subject;
+pub mod journal;
+pub mod goal;
+pub mod log;
 
 type ApplicationContext<'a> = poise::ApplicationContext<'a, Data, Error>;
@@ -0,0 +1,45 @@
+use crate::{notifications::{user_rank_notifications, NOTIFIED_PLACES}, prelude::ActOnUser, Context, Error};
+
+/// Manage your notifications.
+#[poise::command(slash_command, prefix_command, subcommands("leaderboard"), ephemeral = true)]
+pub async fn notifications(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Get notified when someone passes you on the leaderboard.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "Whether to notify you (toggles if empty)"]
+    enabled: Option<bool>
+) -> Result<(), Error> {
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+    let uid = act_on_user_ctx.uid();
+
+    let enabled = match enabled {
+        Some(e) => e,
+        None => !user_rank_notifications(act_on_user_ctx).await
+    };
+
+    if enabled {
+        sqlx::query!("
+        INSERT OR IGNORE INTO rank_change_notifications (user_id)
+        SELECT id FROM users WHERE uid = $1
+        ", uid)
+            .execute(act_on_user_ctx.0)
+            .await?;
+
+        ctx.reply(format!("You will now be notified when someone passes you in the top {} of the leaderboard.", NOTIFIED_PLACES)).await?;
+    } else {
+        sqlx::query!("
+        DELETE FROM rank_change_notifications
+        WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+        ", uid)
+            .execute(act_on_user_ctx.0)
+            .await?;
+
+        ctx.reply("You will no longer be notified about leaderboard changes.").await?;
+    }
+
+    Ok(())
+}
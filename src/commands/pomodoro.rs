@@ -0,0 +1,87 @@
+use std::num::NonZeroU64;
+
+use crate::{pomodoro::{start_pomodoro, stop_pomodoro, PomodoroTarget}, study::is_study_vc, Context, Error, PomodoroLengths};
+
+/// Study in Pomodoro cycles.
+#[poise::command(slash_command, prefix_command, subcommands("start", "stop"), ephemeral = true)]
+pub async fn pomodoro(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Cycle for you alone, or for everyone in your voice channel.
+fn pomodoro_target(ctx: Context<'_>, for_channel: bool) -> Result<PomodoroTarget, Error> {
+    if !for_channel {
+        return Ok(PomodoroTarget::User(ctx.author().id))
+    }
+
+    let guild = ctx.guild()
+        .ok_or(Error::from("Channel Pomodoros can only be used in a server."))?;
+    let channel_id = guild.voice_states
+        .get(&ctx.author().id)
+        .and_then(|vs| vs.channel_id)
+        .filter(|&channel_id| is_study_vc(&ctx.data().config.channels, channel_id))
+        .ok_or(Error::from("You need to be in a study voice channel."))?;
+
+    Ok(PomodoroTarget::Channel(guild.id, channel_id))
+}
+
+/// Start a Pomodoro cycle, with breaks taken automatically.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn start(
+    ctx: Context<'_>,
+    #[description = "Run it for everyone in your voice channel"]
+    for_channel: Option<bool>,
+    #[description = "Minutes of work per cycle"]
+    #[min = 1]
+    work: Option<u64>,
+    #[description = "Minutes of a short break"]
+    #[min = 1]
+    short_break: Option<u64>,
+    #[description = "Minutes of a long break"]
+    #[min = 1]
+    long_break: Option<u64>
+) -> Result<(), Error> {
+    let target = pomodoro_target(ctx, for_channel.unwrap_or(false))?;
+
+    if target.users(ctx.serenity_context(), &ctx.data().study_states).await.is_empty() {
+        return Err(Error::from("You are not studying right now."))
+    }
+
+    let defaults = ctx.data().config.pomodoro;
+    let lengths = PomodoroLengths {
+        work_minutes: work.and_then(NonZeroU64::new).unwrap_or(defaults.work_minutes),
+        short_break_minutes: short_break.and_then(NonZeroU64::new).unwrap_or(defaults.short_break_minutes),
+        long_break_minutes: long_break.and_then(NonZeroU64::new).unwrap_or(defaults.long_break_minutes),
+        ..defaults
+    };
+
+    if !start_pomodoro(ctx.serenity_context(), ctx.data(), target, lengths).await {
+        return Err(Error::from("A Pomodoro is already running. Stop it first."))
+    }
+
+    ctx.reply(format!(
+        ":tomato: Pomodoro started: {} minutes of work, {} minute short breaks and {} minute long breaks.",
+        lengths.work_minutes,
+        lengths.short_break_minutes,
+        lengths.long_break_minutes)).await?;
+
+    Ok(())
+}
+
+/// Stop a running Pomodoro cycle.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn stop(
+    ctx: Context<'_>,
+    #[description = "Stop the one for your voice channel"]
+    for_channel: Option<bool>
+) -> Result<(), Error> {
+    let target = pomodoro_target(ctx, for_channel.unwrap_or(false))?;
+
+    if !stop_pomodoro(ctx.serenity_context(), ctx.data(), target).await {
+        return Err(Error::from("No Pomodoro is running."))
+    }
+
+    ctx.reply(":tomato: Pomodoro stopped.").await?;
+
+    Ok(())
+}
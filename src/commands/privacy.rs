@@ -0,0 +1,45 @@
+use crate::{leaderboard::user_leaderboard_optout, prelude::ActOnUser, Context, Error};
+
+/// Manage your privacy settings.
+#[poise::command(slash_command, prefix_command, subcommands("leaderboard"), ephemeral = true)]
+pub async fn privacy(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Hide yourself from the leaderboard, or show yourself again.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "Whether to show you on the leaderboard (toggles if empty)"]
+    visible: Option<bool>
+) -> Result<(), Error> {
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+    let uid = act_on_user_ctx.uid();
+
+    let visible = match visible {
+        Some(v) => v,
+        None => user_leaderboard_optout(act_on_user_ctx).await
+    };
+
+    if visible {
+        sqlx::query!("
+        DELETE FROM leaderboard_optout
+        WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+        ", uid)
+            .execute(act_on_user_ctx.0)
+            .await?;
+
+        ctx.reply("You are now shown on the leaderboard.").await?;
+    } else {
+        sqlx::query!("
+        INSERT OR IGNORE INTO leaderboard_optout
+        SELECT id FROM users WHERE uid = $1
+        ", uid)
+            .execute(act_on_user_ctx.0)
+            .await?;
+
+        ctx.reply("You are now hidden from the leaderboard.").await?;
+    }
+
+    Ok(())
+}
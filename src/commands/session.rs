@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use humantime::format_duration;
+use poise::serenity_prelude::MessageBuilder;
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{boosters::{apply_boosters, user_boosters}, prelude::ActOnUser, study::base_coins, Context, Error};
+
+/// View your study session in progress.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn session(
+    ctx: Context<'_>
+) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+    let config = &ctx.data().config;
+
+    let (start, length, video_length, break_length, not_studying, on_break) = {
+        let study_states = ctx.data().study_states.lock().await;
+        let state = study_states.get(&ctx.author().id)
+            .ok_or(Error::from("You are not studying right now."))?;
+
+        if state.is_away().await {
+            return Err(Error::from("You are not studying right now."))
+        }
+
+        let length = state.length_at(now).await;
+        (
+            state.start,
+            length,
+            state.video_length_at(now).await,
+            state.break_length_at(now).await.min(length),
+            state.not_studying_at(now).await,
+            state.on_break().await
+        )
+    };
+
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+    let uid = act_on_user_ctx.uid();
+
+    let base_coins = base_coins(&config.study_earnings, length, break_length);
+    let boosters = user_boosters(act_on_user_ctx, start, now).await;
+    let coins = apply_boosters(base_coins, &boosters, config.study_earnings.booster_stacking, start, now, &not_studying);
+
+    let video_time_left = sqlx::query!("
+    SELECT time_left FROM video_rewards_time_left
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ", uid)
+        .fetch_optional(act_on_user_ctx.0)
+        .await?
+        .map(|r| Duration::from_secs(r.time_left as u64));
+
+    let whole_secs = |d: Duration| format_duration(Duration::from_secs(d.as_secs())).to_string();
+
+    let mut b = MessageBuilder::new();
+
+    b.push(":stopwatch: ");
+    b.push_bold(whole_secs(length));
+    b.push_line(format!(" studied since <t:{}:t>", start.unix_timestamp()));
+
+    if on_break || !break_length.is_zero() {
+        b.push(":coffee: ");
+        b.push_bold(whole_secs(break_length));
+        b.push(" of breaks");
+        if on_break {
+            b.push(" (on a break now)");
+        }
+        b.push_line("");
+    }
+
+    if !video_length.is_zero() {
+        b.push(":video_camera: ");
+        b.push_bold(whole_secs(video_length));
+        b.push_line(" of video streamed");
+    }
+
+    match video_time_left.map(|left| left.checked_sub(video_length)) {
+        Some(Some(left)) => {
+            b.push(":gift: ");
+            b.push_bold(whole_secs(left));
+            b.push_line(" of video streaming left until next reward");
+        }
+        Some(None) => {
+            b.push_line(":gift: Video reward reached! You will get it when the session ends.");
+        }
+        None => ()
+    }
+
+    b.push(":purse: ");
+    b.push_bold(format!("+{}", coins));
+    if boosters.is_empty() {
+        b.push_line(" coins so far");
+    } else {
+        b.push_line(format!(" coins so far ({} base)", base_coins));
+
+        for booster in &boosters {
+            b.push("-# :zap: ");
+            b.push_bold(format!("{}x", booster.multiplier as f64 / 100.0));
+            b.push_line(format!(" booster, expires <t:{}:R>", booster.expiration.unix_timestamp()));
+        }
+    }
+
+    ctx.reply(b.build()).await?;
+
+    Ok(())
+}
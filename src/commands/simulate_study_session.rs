@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use poise::serenity_prelude::User;
-use tokio::time::Instant;
+use sqlx::types::time::OffsetDateTime;
 
 use crate::{study::{finish_session, StudyState}, Context, Error};
 
@@ -25,15 +25,21 @@ pub async fn simulate_study_session(
         return Err(Error::from("Total length must be greater than or equals to the video length."))
     }
 
+    let now = OffsetDateTime::now_utc();
+
     let study_state = StudyState {
-        start: Instant::now() - length,
+        start: now - length,
         video_start: None.into(),
         video_sum: video_length.into(),
         break_start: None.into(),
-        break_sum: Duration::ZERO.into()
+        break_sum: Duration::ZERO.into(),
+        breaks: Vec::new().into(),
+        pomodoro_break: false.into(),
+        left_at: None.into(),
+        absences: Vec::new().into()
     };
 
-    finish_session(ctx.serenity_context(), ctx.data(), user.id, study_state, alert).await;
+    finish_session(ctx.serenity_context(), ctx.data(), user.id, study_state, now, alert).await;
 
     let _ = ctx.reply("Session simulated.").await;
 
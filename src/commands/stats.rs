@@ -1,20 +1,21 @@
-use std::{fs::{remove_file, File}, path::Path, sync::Arc, time::Duration};
+use std::{collections::HashMap, fs::{remove_file, File}, path::Path, time::Duration};
 
-use charming::{component::{Axis, Title}, element::AxisType, series::Line, theme::Theme, Chart, ImageRenderer};
+use charming::{component::{Axis, Title}, element::AxisType, series::{Line, Pie}, theme::Theme, Chart, ImageRenderer};
 use chrono::{NaiveDate, Utc};
 use humantime::{format_duration, parse_duration};
-use poise::{serenity_prelude::{AutocompleteChoice, CreateAllowedMentions, CreateAttachment, CreateMessage, MessageBuilder, User}, CreateReply};
+use poise::{serenity_prelude::{AutocompleteChoice, CreateAllowedMentions, CreateAttachment, CreateMessage, MessageBuilder, User}, ChoiceParameter, CreateReply};
 use rand::Rng;
-use resvg::{tiny_skia::{Pixmap, PixmapMut}, usvg::{Options, Transform, Tree}};
+use sqlx::types::time::OffsetDateTime;
 
-use crate::{leaderboard::{real_leaderboard_start_datetime, user_place}, prelude::{user_balance, ActOnUser}, study::user_streak, Context, Error};
+use crate::{goals::{goal_completions, user_goal_progress, GoalPeriod}, inventory::{user_item_amount, Item}, leaderboard::{user_place, LeaderboardMetric, LeaderboardPeriod}, prelude::{user_balance, ActOnUser}, commands::subject::autocomplete_subject, render::render_png, streak::user_streak, timezone::{from_naive, local_date, local_midnight, split_across_days, user_timezone}, Context, Error};
 
 #[derive(poise::ChoiceParameter)]
 enum Statistic {
     Time,
     #[name = "Video time"]
     VideoTime,
-    Balance
+    Balance,
+    Subjects
 }
 
 async fn autocomplete_period(
@@ -37,6 +38,25 @@ async fn autocomplete_period(
     })
 }
 
+async fn send_chart(ctx: Context<'_>, chart: &Chart) -> Result<(), Error> {
+    let mut renderer =
+        Box::new(
+            ImageRenderer::new(1024,512)
+            .theme(Theme::Walden));
+
+    let svg_string = renderer.render(chart)?;
+    drop(renderer);
+
+    let png = render_png(&svg_string, HashMap::new())?;
+
+    ctx.send(
+        CreateReply::default()
+        .attachment(CreateAttachment::bytes(png, "chart.png"))
+    ).await?;
+
+    Ok(())
+}
+
 /// View user statistics and data.
 #[poise::command(slash_command, prefix_command, ephemeral)]
 pub async fn stats(
@@ -50,133 +70,142 @@ pub async fn stats(
     #[description = "Period"]
     #[autocomplete = "autocomplete_period"]
     period: Option<String>,
+    #[description = "Only count sessions of this subject"]
+    #[autocomplete = "autocomplete_subject"]
+    subject: Option<String>,
 ) -> Result<(), Error> {
     let user = user.as_ref().unwrap_or_else(|| ctx.author());
+    let tz = user_timezone(&ActOnUser(&ctx.data().db_pool, user.id)).await;
 
     match statistic {
         Some(stat) => {
             let uid = i64::from(user.id);
+            let pool = &ctx.data().db_pool;
 
             let date = date.map(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d"))
-                .unwrap_or(Ok(Utc::now().date_naive()))?;
+                .unwrap_or(Ok(Utc::now().with_timezone(&tz).date_naive()))?;
             let period = period.map(|p| parse_duration(&p))
                 .unwrap_or(Ok(Duration::from_secs((30.5 * 24.0 * 60.0 * 60.0) as u64)))?;
 
-            let start = (date - chrono::Duration::from_std(period)?).to_string();
-            let end = date.to_string();
-
-            // TODO this is not ideal, very hacky
-            let dates = sqlx::query!("
-            WITH RECURSIVE date_range AS (
-                SELECT DATE($1) AS date
-                UNION ALL
-                SELECT DATE(date, '+1 day')
-                FROM date_range
-                WHERE DATE(date, '+1 day') <= DATE($2)
-            ) SELECT * FROM date_range
-            ", start, end)
-                .fetch_all(&ctx.data().db_pool)
-                .await.unwrap();
+            let start = from_naive(date - chrono::Duration::from_std(period)?);
+            let end = from_naive(date);
+
+            let dates = (0..=(end - start).whole_days())
+                .map(|i| start + time::Duration::days(i))
+                .collect::<Vec<_>>();
+
+            // Days start at midnight in the user's timezone.
+            let range_start = local_midnight(start, tz);
+            let range_end = local_midnight(end.next_day().unwrap(), tz);
+            let day_index = |at: OffsetDateTime| (local_date(at, tz) - start).whole_days() as usize;
 
             let (title, y_axis_label, data) = match stat {
-                Statistic::Time => {
-                    let data = sqlx::query!("
-                    WITH RECURSIVE date_range AS (
-                        SELECT DATE($1) AS date
-                        UNION ALL
-                        SELECT DATE(date, '+1 day')
-                        FROM date_range
-                        WHERE DATE(date, '+1 day') <= DATE($2)
-                    ),
-                    sessions_with_dates AS (
-                        SELECT id, user_id, length, date
-                        FROM date_range
-                        LEFT JOIN study_sessions
-                            ON date = DATE(ended)
-                    )
-                    SELECT uid, date, COALESCE(SUM(length), 0) AS daily_time
-                    FROM sessions_with_dates
-                    LEFT JOIN users
-                        ON user_id = users.id
-                    WHERE uid IS NULL OR uid = $3
-                    GROUP BY user_id, date
-                    ORDER BY date
-                    ", start, end, uid)
-                        .fetch_all(&ctx.data().db_pool)
+                Statistic::Time | Statistic::VideoTime => {
+                    let sessions = sqlx::query!(r#"
+                    SELECT
+                        started AS "started: OffsetDateTime",
+                        ended AS "ended: OffsetDateTime",
+                        length,
+                        video_length
+                    FROM study_sessions
+                    JOIN users ON user_id = users.id
+                    WHERE
+                        uid = $1 AND ended >= $2 AND COALESCE(started, ended) < $3 AND
+                        ($4 IS NULL OR study_sessions.id IN (SELECT session_id FROM session_tags WHERE subject = $4))
+                    "#, uid, range_start, range_end, subject)
+                        .fetch_all(pool)
                         .await.unwrap();
 
-                    ("Study time", "hours/day",
-                     data
-                     .iter()
-                     .map(|r| r.daily_time as f64 / 3600.0)
-                     .collect::<Vec<_>>())
-                }
-                Statistic::VideoTime => {
-                    let data = sqlx::query!("
-                    WITH RECURSIVE date_range AS (
-                        SELECT DATE($1) AS date
-                        UNION ALL
-                        SELECT DATE(date, '+1 day')
-                        FROM date_range
-                        WHERE DATE(date, '+1 day') <= DATE($2)
-                    ),
-                    sessions_with_dates AS (
-                        SELECT id, user_id, video_length, date
-                        FROM date_range
-                        LEFT JOIN study_sessions
-                            ON date = DATE(ended)
-                    )
-                    SELECT uid, date, COALESCE(SUM(video_length), 0) AS daily_video_time
-                    FROM sessions_with_dates
-                    LEFT JOIN users
-                        ON user_id = users.id
-                    WHERE uid IS NULL OR uid = $3
-                    GROUP BY user_id, date
-                    ORDER BY date
-                    ", start, end, uid)
-                        .fetch_all(&ctx.data().db_pool)
-                        .await.unwrap();
+                    // Sessions that span midnight count towards each day they were on.
+                    let mut daily_time = vec![0.0; dates.len()];
+                    for session in sessions {
+                        let amount = match stat {
+                            Statistic::VideoTime => session.video_length,
+                            _ => session.length
+                        } as f64;
+                        let started = session.started
+                            .unwrap_or(session.ended - time::Duration::seconds(session.length));
+
+                        for (day, part) in split_across_days(started, session.ended, tz) {
+                            let i = (day - start).whole_days();
+                            if (0..dates.len() as i64).contains(&i) {
+                                daily_time[i as usize] += amount * part;
+                            }
+                        }
+                    }
 
-                    ("Video time", "hours/day",
-                     data
+                    let title = match stat {
+                        Statistic::VideoTime => "Video time",
+                        _ => "Study time"
+                    };
+
+                    (match &subject {
+                        Some(subject) => format!("{}: {}", title, subject),
+                        None => title.to_string()
+                    }, "hours/day",
+                     daily_time
                      .iter()
-                     .map(|r| r.daily_video_time as f64 / 3600.0)
+                     .map(|&t| t / 3600.0)
                      .collect::<Vec<_>>())
                 }
                 Statistic::Balance => {
-                    let data = sqlx::query!("
-                    WITH RECURSIVE date_range AS (
-                        SELECT DATE($1) AS date
-                        UNION ALL
-                        SELECT DATE(date, '+1 day')
-                        FROM date_range
-                        WHERE DATE(date, '+1 day') <= DATE($2)
-                    ),
-                    transactions_with_dates AS (
-                        SELECT id, user_id, coins_diff, timestamp, date
-                        FROM date_range
-                        LEFT JOIN coin_transactions
-                            ON date = DATE(timestamp, 'unixepoch')
-                    )
-                    SELECT
-                        uid, date,
-                        COALESCE(SUM(coins_diff) OVER (ORDER BY date), 0) AS balance
-                    FROM transactions_with_dates
-                    LEFT JOIN users
-                        ON user_id = users.id
-                    WHERE uid IS NULL OR uid = $3
-                    GROUP BY user_id, date
-                    ORDER BY date
-                    ", start, end, uid)
-                        .fetch_all(&ctx.data().db_pool)
+                    let range_start = range_start.unix_timestamp();
+                    let range_end = range_end.unix_timestamp();
+
+                    let transactions = sqlx::query!("
+                    SELECT coins_diff, timestamp
+                    FROM coin_transactions
+                    JOIN users ON user_id = users.id
+                    WHERE uid = $1 AND timestamp < $2
+                    ORDER BY timestamp
+                    ", uid, range_end)
+                        .fetch_all(pool)
                         .await.unwrap();
 
-                    ("Balance", "Coins",
-                         data
+                    let mut daily_diff = vec![0i64; dates.len()];
+                    let mut balance = 0;
+                    for transaction in transactions {
+                        if transaction.timestamp < range_start {
+                            balance += transaction.coins_diff;
+                        } else {
+                            let at = OffsetDateTime::from_unix_timestamp(transaction.timestamp)?;
+                            daily_diff[day_index(at)] += transaction.coins_diff;
+                        }
+                    }
+
+                    ("Balance".to_string(), "Coins",
+                         daily_diff
                          .iter()
-                         .map(|r| r.balance as f64)
+                         .map(|diff| {
+                             balance += diff;
+                             balance as f64
+                         })
                          .collect::<Vec<_>>())
                 }
+                Statistic::Subjects => {
+                    let subjects = sqlx::query!(r#"
+                    SELECT COALESCE(subject, 'No subject') AS "subject!: String", SUM(length) AS "time!: i64"
+                    FROM study_sessions
+                    JOIN users ON user_id = users.id
+                    LEFT JOIN session_tags ON session_id = study_sessions.id
+                    WHERE uid = $1 AND ended >= $2 AND ended < $3
+                    GROUP BY subject
+                    ORDER BY 2 DESC
+                    "#, uid, range_start, range_end)
+                        .fetch_all(pool)
+                        .await.unwrap();
+
+                    let chart = Chart::new()
+                        .title(Title::new().text("Study time per subject (hours)"))
+                        .series(Pie::new()
+                            .radius("65%")
+                            .data(subjects
+                                .iter()
+                                .map(|r| ((r.time as f64 / 3600.0 * 10.0).round() / 10.0, r.subject.as_str()))
+                                .collect()));
+
+                    return send_chart(ctx, &chart).await
+                }
             };
 
             let path = &format!(
@@ -193,45 +222,47 @@ pub async fn stats(
                     .data(
                         dates
                         .iter()
-                        .flat_map(|r| &r.date)
+                        .map(|d| d.to_string())
                         .collect()))
                 .y_axis(Axis::new().type_(AxisType::Value).name(y_axis_label))
                 .series(Line::new().data(data));
 
-            let mut renderer =
-                Box::new(
-                    ImageRenderer::new(1024,512)
-                    .theme(Theme::Walden));
-
-            let svg_string = renderer.render(&chart)?;
-            drop(renderer);
+            send_chart(ctx, &chart).await?;
+        }
+        None => {
+            let act_on_user_ctx = ActOnUser(&ctx.data().db_pool, user.id);
 
-            let mut font_db = resvg::usvg::fontdb::Database::new();
-            font_db.load_system_fonts();
+            let balance = user_balance(&act_on_user_ctx).await;
+            let (lb_start, lb_end) = LeaderboardPeriod::Month.bounds();
+            let place = user_place(&act_on_user_ctx, &ctx.data().config.study_rules, LeaderboardMetric::StudyTime, lb_start, lb_end).await;
+            let streak = user_streak(&act_on_user_ctx, &ctx.data().config.study_rules).await;
+            let freezes = user_item_amount(&act_on_user_ctx, Item::StreakFreeze).await;
 
-            let options = Options {
-                fontdb: Arc::new(font_db),
-                ..Default::default()
-            };
-            let rtree = Tree::from_str(&svg_string, &options)?;
+            let goals = {
+                let mut b = MessageBuilder::new();
+                let progress = user_goal_progress(&act_on_user_ctx).await;
 
-            let size = rtree.size();
-            let mut pixmap = Pixmap::new(size.width() as u32, size.height() as u32).unwrap();
-            resvg::render(&rtree, Transform::identity(), &mut pixmap.as_mut());
+                for period in GoalPeriod::ALL {
+                    let (completions, last) = goal_completions(&act_on_user_ctx, period).await;
 
-            let png = pixmap.encode_png()?;
+                    if let Some(goal) = progress.iter().find(|g| g.period == period) {
+                        b.push(":dart: ");
+                        b.push_bold(format_duration(Duration::from_secs(goal.studied.as_secs() / 60 * 60)).to_string());
+                        b.push_line(format!(" / {} {}", format_duration(goal.goal), period.current()));
+                    } else if completions == 0 {
+                        continue
+                    }
 
-            ctx.send(
-                CreateReply::default()
-                .attachment(CreateAttachment::bytes(png, "chart.png"))
-            ).await?;
-        }
-        None => {
-            let act_on_user_ctx = ActOnUser(&ctx.data().db_pool, user.id);
+                    b.push_line(format!("-# {} goal met **{}** times{}",
+                        period.name(),
+                        completions,
+                        last
+                        .map(|l| format!(", last on <t:{}:D>", l.unix_timestamp()))
+                            .unwrap_or_default()));
+                }
 
-            let balance = user_balance(&act_on_user_ctx).await;
-            let place = user_place(&act_on_user_ctx, real_leaderboard_start_datetime()).await;
-            let streak = user_streak(&act_on_user_ctx).await;
+                b.build()
+            };
 
             ctx.send(CreateReply::default()
                 .content(
@@ -246,8 +277,27 @@ pub async fn stats(
                             .unwrap_or("Not in leaderboard".to_string()))
 
                     .push(":wing: ")
-                    .push_bold(streak.to_string())
-                    .push_line(" day streak")
+                    .push_bold(streak.current.to_string())
+                    .push(" day streak")
+                    .push_line(
+                        streak.start
+                        .map(|d| format!(" since <t:{}:D>", local_midnight(d, tz).unix_timestamp()))
+                            .unwrap_or_default())
+                    .push_line(format!("-# Longest streak: **{}** days{}",
+                        streak.longest,
+                        if streak.current > 0 && !streak.today_counted {
+                            " - study today to keep it going!"
+                        } else if streak.freezable && !streak.today_counted && freezes > 0 {
+                            " - study today and a streak freeze will save your streak!"
+                        } else {
+                            ""
+                        }))
+
+                    .push(goals)
+
+                    .push(":ice_cube: ")
+                    .push_bold(freezes.to_string())
+                    .push_line(" streak freezes")
 
                     .push(":purse: ")
                     .push_bold(balance.to_string())
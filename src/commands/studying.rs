@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use humantime::format_duration;
+use poise::{serenity_prelude::{CreateAllowedMentions, Mentionable, MessageBuilder}, CreateReply};
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{Context, Error};
+
+/// See who is studying right now.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn studying(
+    ctx: Context<'_>
+) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+
+    let mut studying = Vec::new();
+    for (user_id, state) in ctx.data().study_states.lock().await.iter() {
+        if state.is_away().await {
+            continue
+        }
+
+        studying.push((
+            *user_id,
+            state.start,
+            state.length_at(now).await,
+            state.video_start.lock().await.is_some(),
+            state.on_break().await));
+    }
+    studying.sort_by_key(|(_, start, _, _, _)| *start);
+
+    let mut b = MessageBuilder::new();
+
+    b.push_line(format!("## :books: Studying now: {}", studying.len()));
+
+    if studying.is_empty() {
+        b.push_line("-# Nobody is studying right now.");
+    }
+
+    for (user_id, _, length, video, on_break) in studying {
+        b.push(format!("{} - ", user_id.mention()));
+        b.push_bold(format_duration(Duration::from_secs(length.as_secs() / 60 * 60)).to_string());
+        if video {
+            b.push(" :video_camera:");
+        }
+        if on_break {
+            b.push(" :coffee:");
+        }
+        b.push_line("");
+    }
+
+    ctx.send(CreateReply::default()
+        .content(b.build())
+        .allowed_mentions(CreateAllowedMentions::new())).await?;
+
+    Ok(())
+}
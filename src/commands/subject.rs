@@ -0,0 +1,57 @@
+use poise::serenity_prelude::AutocompleteChoice;
+
+use crate::{prelude::ActOnUser, subjects::{user_subjects, MAX_SUBJECT_LENGTH}, Context, Error};
+
+pub async fn autocomplete_subject(
+    ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+
+    user_subjects(&ActOnUser(&ctx.data().db_pool, ctx.author().id)).await
+        .into_iter()
+        .filter(move |s| s.to_lowercase().contains(&partial))
+        .map(|s| AutocompleteChoice::new(s.clone(), s))
+}
+
+/// Choose the subject your study sessions get when they end.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn subject(
+    ctx: Context<'_>,
+    #[description = "What you are studying (clears it if empty)"]
+    #[autocomplete = "autocomplete_subject"]
+    #[max_length = 50]
+    subject: Option<String>
+) -> Result<(), Error> {
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+    let uid = act_on_user_ctx.uid();
+
+    match subject.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(subject) => {
+            if subject.chars().count() > MAX_SUBJECT_LENGTH {
+                return Err(Error::from(format!("Subjects can be at most {} characters long.", MAX_SUBJECT_LENGTH)))
+            }
+
+            sqlx::query!("
+            INSERT OR REPLACE INTO subject_presets (user_id, subject)
+            SELECT id, $2 FROM users WHERE uid = $1
+            ", uid, subject)
+                .execute(act_on_user_ctx.0)
+                .await?;
+
+            ctx.reply(format!(":label: Your sessions will be tagged with **{}**.", subject)).await?;
+        }
+        None => {
+            sqlx::query!("
+            DELETE FROM subject_presets
+            WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+            ", uid)
+                .execute(act_on_user_ctx.0)
+                .await?;
+
+            ctx.reply(":label: Your sessions will no longer be tagged automatically.").await?;
+        }
+    }
+
+    Ok(())
+}
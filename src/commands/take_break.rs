@@ -0,0 +1,20 @@
+use poise::{serenity_prelude::CreateActionRow, CreateReply};
+
+use crate::{study::{break_message, toggle_user_break}, Context, Error};
+
+/// Take a break from studying, or resume after one.
+#[poise::command(slash_command, prefix_command, rename = "break", ephemeral = true)]
+pub async fn take_break(
+    ctx: Context<'_>
+) -> Result<(), Error> {
+    let on_break = toggle_user_break(ctx.data(), ctx.author().id).await
+        .ok_or(Error::from("You are not studying right now."))?;
+
+    let (content, button) = break_message(on_break);
+
+    ctx.send(CreateReply::default()
+        .content(content)
+        .components(vec![CreateActionRow::Buttons(vec![button])])).await?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use chrono::Utc;
+use chrono_tz::{Tz, TZ_VARIANTS};
+use poise::serenity_prelude::AutocompleteChoice;
+
+use crate::{prelude::ActOnUser, timezone::user_timezone, Context, Error};
+
+async fn autocomplete_timezone(
+    _ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+
+    TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name())
+        .filter(move |name| name.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|name| AutocompleteChoice::new(name, name))
+}
+
+/// Set your timezone, so your days start at your midnight.
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn timezone(
+    ctx: Context<'_>,
+    #[description = "IANA timezone, like Europe/Stockholm (shows your timezone if empty)"]
+    #[autocomplete = "autocomplete_timezone"]
+    timezone: Option<String>
+) -> Result<(), Error> {
+    let act_on_user_ctx = &ActOnUser(&ctx.data().db_pool, ctx.author().id);
+    let uid = act_on_user_ctx.uid();
+
+    let Some(timezone) = timezone else {
+        let tz = user_timezone(act_on_user_ctx).await;
+        ctx.reply(format!("Your timezone is **{}** (it is {} there).",
+            tz.name(),
+            Utc::now().with_timezone(&tz).format("%H:%M"))).await?;
+        return Ok(())
+    };
+
+    let tz = timezone.parse::<Tz>()
+        .map_err(|_| Error::from(format!("Unknown timezone `{}`.", timezone)))?;
+    let name = tz.name();
+
+    sqlx::query!("
+    INSERT OR REPLACE INTO user_timezones (user_id, timezone)
+    SELECT id, $2 FROM users WHERE uid = $1
+    ", uid, name)
+        .execute(act_on_user_ctx.0)
+        .await?;
+
+    ctx.reply(format!("Your timezone is now **{}**. Streaks and daily stats will count days from your midnight.", name)).await?;
+
+    Ok(())
+}
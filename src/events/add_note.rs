@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use poise::{modal, serenity_prelude::{ComponentInteraction, Context, CreateActionRow, EditMessage}};
+
+use crate::{journal::{session_note, write_session_note, MAX_NOTE_LENGTH}, prelude::ActOnUser, study::result_buttons, subjects::{session_subject, subject_menu, user_subjects}, Data, Error};
+
+#[derive(poise::Modal)]
+#[name = "Session note"]
+struct NoteModal {
+    /// Left empty to remove the note.
+    #[name = "What did you accomplish?"]
+    #[placeholder = "e.g. \"Finished chapter 3 and the exercises\""]
+    #[paragraph]
+    #[max_length = 1000]
+    note: Option<String>
+}
+
+pub async fn add_note(ctx: &Context, interaction: &ComponentInteraction, data: &Data, session_id: i64) -> Result<(), Error> {
+    let uid = i64::from(interaction.user.id);
+    sqlx::query!("
+    SELECT study_sessions.id FROM study_sessions
+    JOIN users ON user_id = users.id
+    WHERE
+        study_sessions.id = $1 AND
+        uid = $2
+    ", session_id, uid)
+        .fetch_optional(&data.db_pool)
+        .await?
+        .ok_or(Error::from("Cannot find that study session."))?;
+
+    let note = modal::execute_modal_on_component_interaction(
+        Box::new(ctx.clone()),
+        interaction.clone(),
+        Some(NoteModal { note: session_note(&data.db_pool, session_id).await }),
+        Some(Duration::from_secs(10 * 60))
+    ).await?
+        .ok_or(Error::from("Failure retrieving modal data."))?
+        .note
+        .unwrap_or_default();
+
+    let note = note.trim();
+    if note.chars().count() > MAX_NOTE_LENGTH {
+        return Err(Error::from(format!("Notes can be at most {} characters long.", MAX_NOTE_LENGTH)))
+    }
+
+    write_session_note(&data.db_pool, session_id, note).await;
+
+    let subjects = user_subjects(&ActOnUser(&data.db_pool, interaction.user.id)).await;
+    let subject = session_subject(&data.db_pool, session_id).await;
+
+    let components = vec![
+        CreateActionRow::SelectMenu(subject_menu(session_id, &subjects, subject.as_deref())),
+        result_buttons(session_id, !note.is_empty())
+    ];
+
+    interaction.message.clone().edit(ctx, EditMessage::new().components(components)).await?;
+
+    Ok(())
+}
@@ -84,7 +84,8 @@ pub async fn deduct_session(ctx: &Context, interaction: &ComponentInteraction, d
             UPDATE study_sessions
             SET
                 length = $2,
-                video_length = $3
+                video_length = $3,
+                break_length = MIN(break_length, $2)
             WHERE id = $1
             ", session_id, new_length, new_video_length)
                 .execute(&data.db_pool)
@@ -4,7 +4,7 @@ use regex::Regex;
 
 use crate::{events::reveal_reward::reveal_reward, Data, Error};
 
-use super::deduct_session::deduct_session;
+use super::{add_note::add_note, deduct_session::deduct_session, journal_page::show_journal_page, leaderboard_page::show_leaderboard_page, review_log::review_log, tag_session::tag_session, toggle_break::toggle_break};
 
 pub async fn interaction_handler(ctx: &Context, data: &Data, interaction: &Interaction) -> Result<(), Error> {
     match interaction {
@@ -58,6 +58,7 @@ pub async fn interaction_handler(ctx: &Context, data: &Data, interaction: &Inter
                         }
                     }
                 }
+                "toggle_break" => toggle_break(ctx, component_interaction, data).await?,
                 id => {
                     if let Some(c) = Regex::new(r"deduct_session_(\d+)").unwrap().captures(id) {
                         deduct_session(
@@ -69,6 +70,35 @@ pub async fn interaction_handler(ctx: &Context, data: &Data, interaction: &Inter
                             ctx, component_interaction, data,
                             c[1].parse::<i64>()?
                         ).await?;
+                    } else if let Some(c) = Regex::new(r"tag_session_(\d+)").unwrap().captures(id) {
+                        tag_session(
+                            ctx, component_interaction, data,
+                            c[1].parse::<i64>()?
+                        ).await?;
+                    } else if let Some(c) = Regex::new(r"add_note_(\d+)").unwrap().captures(id) {
+                        add_note(
+                            ctx, component_interaction, data,
+                            c[1].parse::<i64>()?
+                        ).await?;
+                    } else if let Some(c) = Regex::new(r"journal_page_(\d+)").unwrap().captures(id) {
+                        show_journal_page(
+                            ctx, component_interaction, data,
+                            c[1].parse::<u32>()?
+                        ).await?;
+                    } else if let Some(c) = Regex::new(r"(approve|reject)_log_(\d+)").unwrap().captures(id) {
+                        review_log(
+                            ctx, component_interaction, data,
+                            c[2].parse::<i64>()?,
+                            &c[1] == "approve"
+                        ).await?;
+                    } else if let Some(c) = Regex::new(r"leaderboard_page_(\d+)_(\d+)_(-?\d+)_(-?\d+)").unwrap().captures(id) {
+                        show_leaderboard_page(
+                            ctx, component_interaction, data,
+                            c[1].parse::<u32>()?,
+                            c[2].parse::<u8>()?,
+                            c[3].parse::<i64>()?,
+                            c[4].parse::<i64>()?
+                        ).await?;
                     }
                 }
             }
@@ -0,0 +1,17 @@
+use poise::serenity_prelude::{ComponentInteraction, Context, CreateAllowedMentions, CreateInteractionResponse, CreateInteractionResponseMessage};
+
+use crate::{journal::journal_page, prelude::ActOnUser, Data, Error};
+
+pub async fn show_journal_page(ctx: &Context, interaction: &ComponentInteraction, data: &Data, page: u32) -> Result<(), Error> {
+    let (content, components) = journal_page(
+        &ActOnUser(&data.db_pool, interaction.user.id),
+        page).await;
+
+    interaction.create_response(&ctx, CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+            .content(content)
+            .components(components)
+            .allowed_mentions(CreateAllowedMentions::new()))).await?;
+
+    Ok(())
+}
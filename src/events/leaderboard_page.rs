@@ -0,0 +1,22 @@
+use poise::serenity_prelude::{ComponentInteraction, Context, CreateAllowedMentions, CreateInteractionResponse, CreateInteractionResponseMessage};
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{leaderboard::{leaderboard_page, LeaderboardMetric}, prelude::ActOnUser, Data, Error};
+
+pub async fn show_leaderboard_page(ctx: &Context, interaction: &ComponentInteraction, data: &Data, page: u32, metric: u8, start: i64, end: i64) -> Result<(), Error> {
+    let (content, components) = leaderboard_page(
+        &ActOnUser(&data.db_pool, interaction.user.id),
+        &data.config.study_rules,
+        LeaderboardMetric::from_repr(metric).ok_or(Error::from("Invalid leaderboard metric."))?,
+        OffsetDateTime::from_unix_timestamp(start)?,
+        OffsetDateTime::from_unix_timestamp(end)?,
+        page).await;
+
+    interaction.create_response(&ctx, CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+            .content(content)
+            .components(components)
+            .allowed_mentions(CreateAllowedMentions::new()))).await?;
+
+    Ok(())
+}
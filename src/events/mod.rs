@@ -1,11 +1,17 @@
 mod interactions;
 mod deduct_session;
 mod reveal_reward;
+mod leaderboard_page;
+mod toggle_break;
+mod tag_session;
+mod add_note;
+mod journal_page;
+mod review_log;
 
 use interactions::interaction_handler;
 use log::info;
 use poise::serenity_prelude::{Context, FullEvent::{self, *}};
-use crate::{study::voice_state_update, Error};
+use crate::{study::{close_stale_sessions, reconcile_voice_states, voice_state_update}, Error};
 
 use crate::{prelude::{create_user, ActOnUser}, Data};
 
@@ -20,6 +26,21 @@ pub async fn event_handler(ctx: &Context, event: &FullEvent, data: &Data) -> Res
             create_user(&ActOnUser(&data.db_pool, new_member.user.id)).await;
             Ok(())
         }
+        Ready { data_about_bot } =>
+        {
+            for unavailable_guild in &data_about_bot.guilds {
+                let guild = ctx.cache.guild(unavailable_guild.id).map(|g| g.clone());
+                if let Some(guild) = guild {
+                    reconcile_voice_states(ctx, data, &guild).await?;
+                }
+            }
+            Ok(())
+        }
+        GuildCreate { guild, .. } =>
+            reconcile_voice_states(ctx, data, guild).await,
+        // Every guild has been received, so users studying in none of them have left.
+        CacheReady { guilds } =>
+            close_stale_sessions(ctx, data, guilds).await,
         VoiceStateUpdate { old, new } =>
             voice_state_update(ctx, data, old.as_ref(), &new).await,
         _ => Ok(())
@@ -0,0 +1,89 @@
+use log::info;
+use poise::serenity_prelude::{CacheHttp, ComponentInteraction, Context, CreateAllowedMentions, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, Mentionable, MessageBuilder};
+
+use crate::{manual_sessions::{approve_submission, describe_submission, describe_time_ranges, overlapping_sessions_in, take_submission}, prelude::try_dm_or_in_guild, Data, Error};
+
+pub async fn review_log(ctx: &Context, interaction: &ComponentInteraction, data: &Data, submission_id: i64, approve: bool) -> Result<(), Error> {
+    // The moderator is told why the review failed, rather than seeing the interaction fail.
+    if let Err(e) = review(ctx, interaction, data, submission_id, approve).await {
+        interaction.create_response(&ctx, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(e.to_string()))).await?;
+    }
+
+    Ok(())
+}
+
+async fn review(ctx: &Context, interaction: &ComponentInteraction, data: &Data, submission_id: i64, approve: bool) -> Result<(), Error> {
+    let is_moderator = interaction.member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_messages());
+
+    if !is_moderator {
+        return Err(Error::from("Only moderators can review logged sessions."))
+    }
+
+    info!("Logged session {} {} by {}", submission_id, if approve { "approved" } else { "rejected" }, interaction.user);
+
+    // The submission is only removed from the queue together with saving the session.
+    let mut tx = data.db_pool.begin().await?;
+
+    let submission = take_submission(&mut tx, submission_id).await?
+        .ok_or(Error::from("This session has already been reviewed."))?;
+
+    let approved = if approve {
+        // The user may have studied in voice at the same time since logging the session.
+        let studying_since = data.study_states.lock().await.get(&submission.user_id).map(|s| s.start);
+        let overlapping = overlapping_sessions_in(
+            &mut tx,
+            i64::from(submission.user_id),
+            studying_since,
+            submission.started,
+            submission.started + submission.length).await?;
+
+        if !overlapping.is_empty() {
+            return Err(Error::from(format!("This session overlaps with time the user has studied or logged since:\n{}", describe_time_ranges(&overlapping))))
+        }
+
+        Some(approve_submission(&mut tx, &data.config.study_earnings, &submission).await?)
+    } else {
+        None
+    };
+
+    tx.commit().await?;
+
+    let user = ctx.http().get_user(submission.user_id).await?;
+
+    let (verdict, dm) = if let Some((session_id, coins)) = approved {
+        (format!(":white_check_mark: Approved by {}", interaction.user.mention()),
+         MessageBuilder::new()
+            .push_line(":white_check_mark: Your logged session was approved!")
+            .push(describe_submission(&submission))
+            .push(":purse: ")
+            .push_bold(format!("+{}", coins))
+            .push_line(" coins")
+            .push("-# Session ID: ")
+            .push_mono_line(session_id.to_string())
+            .build())
+    } else {
+        (format!(":x: Rejected by {}", interaction.user.mention()),
+         MessageBuilder::new()
+            .push_line(":x: Your logged session was rejected.")
+            .push(describe_submission(&submission))
+            .build())
+    };
+
+    interaction.create_response(&ctx, CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+            .content(format!("{}\n{}", interaction.message.content, verdict))
+            .components(Vec::new())
+            .allowed_mentions(CreateAllowedMentions::new()))).await?;
+
+    try_dm_or_in_guild(ctx, data, ctx.http(), &user, CreateMessage::new()
+        .content(dm)
+        .allowed_mentions(CreateAllowedMentions::new())).await;
+
+    Ok(())
+}
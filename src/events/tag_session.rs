@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use poise::{modal, serenity_prelude::{ComponentInteraction, ComponentInteractionDataKind, Context, CreateActionRow, CreateInteractionResponse, CreateInteractionResponseMessage, EditMessage}};
+
+use crate::{journal::session_has_note, prelude::ActOnUser, study::result_buttons, subjects::{subject_menu, tag_session as tag, user_subjects, MAX_SUBJECT_LENGTH, NEW_SUBJECT_VALUE}, Data, Error};
+
+#[derive(poise::Modal)]
+#[name = "New subject"]
+struct SubjectModal {
+    #[name = "Subject"]
+    #[placeholder = "What did you study? (e.g. \"Calculus\")"]
+    #[min_length = 1]
+    #[max_length = 50]
+    subject: String
+}
+
+pub async fn tag_session(ctx: &Context, interaction: &ComponentInteraction, data: &Data, session_id: i64) -> Result<(), Error> {
+    let uid = i64::from(interaction.user.id);
+    sqlx::query!("
+    SELECT study_sessions.id FROM study_sessions
+    JOIN users ON user_id = users.id
+    WHERE
+        study_sessions.id = $1 AND
+        uid = $2
+    ", session_id, uid)
+        .fetch_optional(&data.db_pool)
+        .await?
+        .ok_or(Error::from("Cannot find that study session."))?;
+
+    let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind
+        else { return Err(Error::from("Expected a subject.")) };
+    let value = values.first().ok_or(Error::from("Expected a subject."))?;
+
+    let from_modal = value == NEW_SUBJECT_VALUE;
+    let subject = if from_modal {
+        modal::execute_modal_on_component_interaction(
+            Box::new(ctx.clone()),
+            interaction.clone(),
+            None::<SubjectModal>,
+            Some(Duration::from_secs(5 * 60))
+        ).await?
+            .ok_or(Error::from("Failure retrieving modal data."))?
+            .subject
+    } else {
+        value.strip_prefix("subject:").unwrap_or(value).to_string()
+    };
+
+    let subject = subject.trim();
+    if subject.is_empty() || subject.chars().count() > MAX_SUBJECT_LENGTH {
+        return Err(Error::from(format!("Subjects must be 1 to {} characters long.", MAX_SUBJECT_LENGTH)))
+    }
+
+    tag(&data.db_pool, session_id, subject).await;
+
+    let subjects = user_subjects(&ActOnUser(&data.db_pool, interaction.user.id)).await;
+
+    let components = vec![
+        CreateActionRow::SelectMenu(subject_menu(session_id, &subjects, Some(subject))),
+        result_buttons(session_id, session_has_note(&data.db_pool, session_id).await)
+    ];
+
+    if from_modal {
+        // The interaction was used to open the modal.
+        interaction.message.clone().edit(ctx, EditMessage::new().components(components)).await?;
+    } else {
+        interaction.create_response(&ctx, CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                .components(components))).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use poise::serenity_prelude::{ComponentInteraction, Context, CreateActionRow, CreateInteractionResponse, CreateInteractionResponseMessage};
+
+use crate::{study::{break_message, toggle_user_break}, Data, Error};
+
+pub async fn toggle_break(ctx: &Context, interaction: &ComponentInteraction, data: &Data) -> Result<(), Error> {
+    let on_break = toggle_user_break(data, interaction.user.id).await
+        .ok_or(Error::from("You are not studying right now."))?;
+
+    let (content, button) = break_message(on_break);
+
+    interaction.create_response(&ctx, CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+            .content(content)
+            .components(vec![CreateActionRow::Buttons(vec![button])]))).await?;
+
+    Ok(())
+}
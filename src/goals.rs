@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{leaderboard::LeaderboardPeriod, prelude::ActOnUser, timezone::{local_date, split_across_days, user_timezone}};
+
+#[derive(Clone, Copy, PartialEq, poise::ChoiceParameter)]
+#[repr(u8)]
+pub enum GoalPeriod {
+    Daily = 0,
+    Weekly = 1
+}
+
+impl GoalPeriod {
+    pub const ALL: [GoalPeriod; 2] = [GoalPeriod::Daily, GoalPeriod::Weekly];
+
+    /// How the current period is referred to, e.g. "2h / 4h today".
+    pub fn current(self) -> &'static str {
+        match self {
+            GoalPeriod::Daily => "today",
+            GoalPeriod::Weekly => "this week"
+        }
+    }
+
+    /// Reason of the reward for meeting the goal.
+    pub fn reward_reason(self) -> &'static str {
+        match self {
+            GoalPeriod::Daily => "Daily goal",
+            GoalPeriod::Weekly => "Weekly goal"
+        }
+    }
+
+    fn leaderboard_period(self) -> LeaderboardPeriod {
+        match self {
+            GoalPeriod::Daily => LeaderboardPeriod::Today,
+            GoalPeriod::Weekly => LeaderboardPeriod::Week
+        }
+    }
+}
+
+pub struct GoalProgress {
+    pub period: GoalPeriod,
+    /// Start of the current period.
+    pub start: OffsetDateTime,
+    pub goal: Duration,
+    pub studied: Duration
+}
+
+impl GoalProgress {
+    pub fn is_met(&self) -> bool {
+        self.studied >= self.goal
+    }
+}
+
+pub async fn user_goal(ctx: &ActOnUser<'_>, period: GoalPeriod) -> Option<Duration> {
+    let uid = ctx.uid();
+    let period = period as u8;
+
+    sqlx::query!("
+    SELECT minutes FROM study_goals
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1) AND period = $2
+    ", uid, period)
+        .fetch_optional(ctx.0)
+        .await.unwrap()
+        .map(|r| Duration::from_secs(r.minutes as u64 * 60))
+}
+
+/// Sets the goal for a period, or removes it if none.
+pub async fn set_user_goal(ctx: &ActOnUser<'_>, period: GoalPeriod, goal: Option<Duration>) {
+    let uid = ctx.uid();
+    let period = period as u8;
+
+    match goal.map(|g| (g.as_secs() / 60) as i64).filter(|&m| m > 0) {
+        Some(minutes) => {
+            sqlx::query!("
+            INSERT OR REPLACE INTO study_goals (user_id, period, minutes)
+            SELECT id, $2, $3 FROM users WHERE uid = $1
+            ", uid, period, minutes)
+                .execute(ctx.0)
+                .await.unwrap();
+        }
+        None => {
+            sqlx::query!("
+            DELETE FROM study_goals
+            WHERE user_id IN (SELECT id FROM users WHERE uid = $1) AND period = $2
+            ", uid, period)
+                .execute(ctx.0)
+                .await.unwrap();
+        }
+    }
+}
+
+/// Progress towards each of the user's goals in their current periods.
+pub async fn user_goal_progress(ctx: &ActOnUser<'_>) -> Vec<GoalProgress> {
+    let uid = ctx.uid();
+    let tz = user_timezone(ctx).await;
+
+    let mut progress = Vec::new();
+    for period in GoalPeriod::ALL {
+        let Some(goal) = user_goal(ctx, period).await else { continue };
+        let (start, end) = period.leaderboard_period().bounds_in(tz);
+
+        let (first_day, end_day) = (local_date(start, tz), local_date(end, tz));
+
+        let sessions = sqlx::query!(r#"
+        SELECT
+            started AS "started: OffsetDateTime",
+            ended AS "ended: OffsetDateTime",
+            length,
+            break_length
+        FROM study_sessions
+        JOIN users ON user_id = users.id
+        WHERE uid = $1 AND ended >= $2 AND COALESCE(started, ended) < $3
+        "#, uid, start, end)
+            .fetch_all(ctx.0)
+            .await.unwrap();
+
+        // Like in /stats, sessions that span midnight count towards each day they were on.
+        // Breaks do not count towards the goal.
+        let mut studied = 0.0;
+        for session in sessions {
+            let started = session.started
+                .unwrap_or(session.ended - time::Duration::seconds(session.length));
+            let focused = (session.length - session.break_length) as f64;
+
+            for (day, part) in split_across_days(started, session.ended, tz) {
+                if first_day <= day && day < end_day {
+                    studied += focused * part;
+                }
+            }
+        }
+
+        progress.push(GoalProgress {
+            period,
+            start,
+            goal,
+            studied: Duration::from_secs_f64(studied)
+        });
+    }
+
+    progress
+}
+
+/// Records the goals that are met for the first time in their current period.
+/// Returns the periods of the newly met goals.
+pub async fn complete_goals(ctx: &ActOnUser<'_>, progress: &[GoalProgress]) -> Vec<GoalPeriod> {
+    let uid = ctx.uid();
+
+    let mut completed = Vec::new();
+    for goal in progress.iter().filter(|g| g.is_met()) {
+        let period = goal.period as u8;
+        let period_start = goal.start.unix_timestamp();
+        let minutes = (goal.goal.as_secs() / 60) as i64;
+
+        let inserted = sqlx::query!("
+        INSERT OR IGNORE INTO completed_goals (user_id, period, period_start, minutes)
+        SELECT id, $2, $3, $4 FROM users WHERE uid = $1
+        ", uid, period, period_start, minutes)
+            .execute(ctx.0)
+            .await.unwrap()
+            .rows_affected();
+
+        if inserted > 0 {
+            completed.push(goal.period);
+        }
+    }
+
+    completed
+}
+
+/// How many times the user met a goal, and the start of the latest period they met it in.
+pub async fn goal_completions(ctx: &ActOnUser<'_>, period: GoalPeriod) -> (u32, Option<OffsetDateTime>) {
+    let uid = ctx.uid();
+    let period = period as u8;
+
+    let r = sqlx::query!(r#"
+    SELECT COUNT(*) AS "count!: i64", MAX(period_start) AS "last?: i64"
+    FROM completed_goals
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1) AND period = $2
+    "#, uid, period)
+        .fetch_one(ctx.0)
+        .await.unwrap();
+
+    (r.count as u32, r.last.and_then(|l| OffsetDateTime::from_unix_timestamp(l).ok()))
+}
@@ -0,0 +1,60 @@
+use sqlx::SqliteExecutor;
+
+use crate::prelude::ActOnUser;
+
+#[derive(Clone, Copy)]
+#[repr(u8)]
+pub enum Item {
+    /// Keeps a streak alive over a day without studying.
+    StreakFreeze = 0
+}
+
+pub async fn user_item_amount(ctx: &ActOnUser<'_>, item: Item) -> u64 {
+    let uid = ctx.uid();
+    let item = item as u8;
+
+    sqlx::query!("
+    SELECT amount FROM inventory
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1) AND item = $2
+    ", uid, item)
+        .fetch_optional(ctx.0)
+        .await.unwrap()
+        .map(|r| r.amount as u64)
+        .unwrap_or(0)
+}
+
+pub async fn give_item(ctx: &ActOnUser<'_>, item: Item, amount: u64) {
+    give_item_in(ctx.0, ctx.uid(), item, amount).await.unwrap();
+}
+
+pub async fn give_item_in(executor: impl SqliteExecutor<'_>, uid: i64, item: Item, amount: u64) -> Result<(), sqlx::Error> {
+    let item = item as u8;
+    let amount = amount as i64;
+
+    sqlx::query!("
+    INSERT INTO inventory (user_id, item, amount)
+    SELECT id, $2, $3 FROM users WHERE uid = $1
+    ON CONFLICT (user_id, item) DO UPDATE SET amount = amount + excluded.amount
+    ", uid, item, amount)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Takes one of the item from the user.
+/// False if the user has none.
+pub async fn use_item(ctx: &ActOnUser<'_>, item: Item) -> bool {
+    let uid = ctx.uid();
+    let item = item as u8;
+
+    sqlx::query!("
+    UPDATE inventory
+    SET amount = amount - 1
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1) AND item = $2 AND amount > 0
+    ", uid, item)
+        .execute(ctx.0)
+        .await
+        .unwrap()
+        .rows_affected() != 0
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use humantime::format_duration;
+use poise::serenity_prelude::{ButtonStyle, CreateActionRow, CreateButton, MessageBuilder};
+use sqlx::{SqliteExecutor, SqlitePool};
+
+use crate::prelude::ActOnUser;
+
+/// Longest allowed session note.
+pub const MAX_NOTE_LENGTH: usize = 1000;
+
+const PAGE_SIZE: u32 = 5;
+/// Longest note shown in the journal, so that a full page fits in a message.
+const MAX_SHOWN_NOTE_LENGTH: usize = 280;
+
+pub async fn session_note(pool: &SqlitePool, session_id: i64) -> Option<String> {
+    sqlx::query!("SELECT note FROM session_notes WHERE session_id = $1", session_id)
+        .fetch_optional(pool)
+        .await.unwrap()
+        .map(|r| r.note)
+}
+
+pub async fn session_has_note(pool: &SqlitePool, session_id: i64) -> bool {
+    session_note(pool, session_id).await.is_some()
+}
+
+/// Sets the note of a session, or removes it if empty.
+pub async fn write_session_note(pool: &SqlitePool, session_id: i64, note: &str) {
+    write_session_note_in(pool, session_id, note).await.unwrap();
+}
+
+pub async fn write_session_note_in(executor: impl SqliteExecutor<'_>, session_id: i64, note: &str) -> Result<(), sqlx::Error> {
+    if note.is_empty() {
+        sqlx::query!("DELETE FROM session_notes WHERE session_id = $1", session_id)
+            .execute(executor)
+            .await?;
+    } else {
+        sqlx::query!("
+        INSERT OR REPLACE INTO session_notes (session_id, note)
+        VALUES ($1, $2)
+        ", session_id, note)
+            .execute(executor)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Content and navigation buttons for a page of the user's noted sessions, newest first.
+/// Pages start at zero.
+pub async fn journal_page(ctx: &ActOnUser<'_>, page: u32) -> (String, Vec<CreateActionRow>) {
+    let uid = ctx.uid();
+    // One extra to know if there is a next page.
+    let limit = PAGE_SIZE + 1;
+    let offset = page * PAGE_SIZE;
+
+    let mut entries = sqlx::query!(r#"
+    SELECT
+        UNIXEPOCH(ended) AS "ended!: i64",
+        length,
+        subject AS "subject?",
+        note
+    FROM session_notes
+    JOIN study_sessions ON session_notes.session_id = study_sessions.id
+    JOIN users ON user_id = users.id
+    LEFT JOIN session_tags ON session_tags.session_id = study_sessions.id
+    WHERE uid = $1
+    ORDER BY study_sessions.id DESC
+    LIMIT $2 OFFSET $3
+    "#, uid, limit, offset)
+        .fetch_all(ctx.0)
+        .await.unwrap();
+
+    let has_next = entries.len() > PAGE_SIZE as usize;
+    entries.truncate(PAGE_SIZE as usize);
+
+    let mut b = MessageBuilder::new();
+
+    b.push_line("## :notebook: Journal");
+
+    if entries.is_empty() {
+        b.push_line("-# No notes yet. Use **Add note** under your session results to write one.");
+    }
+
+    for entry in entries {
+        b.push(format!("### <t:{}:D> - ", entry.ended));
+        b.push(format_duration(Duration::from_secs(entry.length as u64)).to_string());
+        if let Some(subject) = entry.subject {
+            b.push(format!(" - :label: {}", subject));
+        }
+        b.push_line("");
+        if entry.note.chars().count() > MAX_SHOWN_NOTE_LENGTH {
+            let shown = entry.note.chars().take(MAX_SHOWN_NOTE_LENGTH - 1).collect::<String>();
+            b.push_line_safe(format!("{}…", shown.trim_end()));
+        } else {
+            b.push_line_safe(entry.note);
+        }
+    }
+
+    let buttons = vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("journal_page_{}", page.saturating_sub(1)))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(format!("journal_page_{}", page + 1))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(!has_next)
+    ])];
+
+    (b.build(), buttons)
+}
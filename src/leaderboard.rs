@@ -1,57 +1,373 @@
-use poise::serenity_prelude::UserId;
+use std::{cmp::Reverse, collections::HashSet, time::Duration};
+
+use chrono_tz::Tz;
+use humantime::format_duration;
+use poise::{serenity_prelude::{ButtonStyle, CreateActionRow, CreateButton, Mentionable, MessageBuilder, UserId}, ChoiceParameter};
 use sqlx::{types::time::{OffsetDateTime, Time}, SqlitePool};
 use time::Date;
 
-use crate::prelude::ActOnUser;
+use crate::{prelude::ActOnUser, streak::all_streaks, timezone::{local_date, local_midnight}, StudyRules};
 
-pub fn real_leaderboard_start_datetime() -> OffsetDateTime {
-    let today = OffsetDateTime::now_utc().date();
+/// Number of places shown per leaderboard page.
+const PAGE_SIZE: u32 = 10;
 
-    OffsetDateTime::new_utc(
-        Date::from_calendar_date(today.year(), today.month(), 1).unwrap(),
-        Time::MIDNIGHT
-    )
+#[derive(Clone, Copy, poise::ChoiceParameter)]
+pub enum LeaderboardPeriod {
+    Today,
+    #[name = "This week"]
+    Week,
+    #[name = "This month"]
+    Month,
+    #[name = "This year"]
+    Year,
+    #[name = "All time"]
+    AllTime
 }
 
-pub async fn user_place(ctx: &ActOnUser<'_>, after: OffsetDateTime) -> Option<u16> {
+impl LeaderboardPeriod {
+    /// Start (inclusive) and end (exclusive) of the current period.
+    pub fn bounds(self) -> (OffsetDateTime, OffsetDateTime) {
+        self.bounds_at(OffsetDateTime::now_utc().date())
+    }
+
+    /// Start (inclusive) and end (exclusive) of the period that contains a day.
+    pub fn bounds_at(self, today: Date) -> (OffsetDateTime, OffsetDateTime) {
+        let (start, end) = self.days_at(today);
+        (OffsetDateTime::new_utc(start, Time::MIDNIGHT), OffsetDateTime::new_utc(end, Time::MIDNIGHT))
+    }
+
+    /// Start (inclusive) and end (exclusive) of the current period, with days starting at midnight in a timezone.
+    pub fn bounds_in(self, tz: Tz) -> (OffsetDateTime, OffsetDateTime) {
+        if let LeaderboardPeriod::AllTime = self {
+            return self.bounds()
+        }
+
+        let (start, end) = self.days_at(local_date(OffsetDateTime::now_utc(), tz));
+        (local_midnight(start, tz), local_midnight(end, tz))
+    }
+
+    /// First day (inclusive) and last day (exclusive) of the period that contains a day.
+    fn days_at(self, today: Date) -> (Date, Date) {
+        match self {
+            LeaderboardPeriod::Today =>
+                (today, today.next_day().unwrap()),
+            LeaderboardPeriod::Week => {
+                let monday = today - time::Duration::days(
+                    today.weekday().number_days_from_monday() as i64);
+                (monday, monday + time::Duration::weeks(1))
+            }
+            LeaderboardPeriod::Month => {
+                let first = Date::from_calendar_date(today.year(), today.month(), 1).unwrap();
+                let next_first = first + time::Duration::days(
+                    time::util::days_in_year_month(today.year(), today.month()) as i64);
+                (first, next_first)
+            }
+            LeaderboardPeriod::Year =>
+                (Date::from_ordinal_date(today.year(), 1).unwrap(),
+                 Date::from_ordinal_date(today.year() + 1, 1).unwrap()),
+            LeaderboardPeriod::AllTime =>
+                (OffsetDateTime::UNIX_EPOCH.date(), Date::MAX)
+        }
+    }
+}
+
+pub async fn user_leaderboard_optout(ctx: &ActOnUser<'_>) -> bool {
     let uid = ctx.uid();
 
     sqlx::query!("
-    SELECT
-        ROW_NUMBER() OVER (ORDER BY SUM(length) DESC) AS place
-    FROM users
-    JOIN study_sessions ON users.id = study_sessions.user_id
-    LEFT JOIN leaderboard_optout ON users.id = leaderboard_optout.user_id
-    WHERE leaderboard_optout.user_id IS NULL AND ended > $1
-    GROUP BY users.id
-    HAVING users.id = $2
-    ", after, uid)
+    SELECT user_id FROM leaderboard_optout
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ", uid)
         .fetch_optional(ctx.0)
         .await
         .unwrap()
-        .map(|r| r.place as u16)
+        .is_some()
 }
 
-pub async fn fetch_leaderboard(pool: &SqlitePool, after: OffsetDateTime, limit: Option<i16>) -> Vec<(UserId, u64)> {
-    let limit = limit.unwrap_or(-1);
+#[derive(Clone, Copy, PartialEq, poise::ChoiceParameter)]
+#[repr(u8)]
+pub enum LeaderboardMetric {
+    #[name = "Study time"]
+    StudyTime = 0,
+    #[name = "Video time"]
+    VideoTime = 1,
+    #[name = "Sessions"]
+    Sessions = 2,
+    #[name = "Current streak"]
+    Streak = 3,
+    #[name = "Balance"]
+    Balance = 4
+}
 
-    sqlx::query!("
-    SELECT
-        users.id AS uid,
-        SUM(length) AS study_amount
-    FROM users
-    JOIN study_sessions ON users.id = study_sessions.user_id
-    LEFT JOIN leaderboard_optout ON users.id = leaderboard_optout.user_id
-    WHERE leaderboard_optout.user_id IS NULL AND ended > $1
-    GROUP BY users.id
-    HAVING SUM(length) IS NOT NULL
-    ORDER BY SUM(length) DESC
-    LIMIT $2
-    ", after, limit)
+impl LeaderboardMetric {
+    pub fn from_repr(repr: u8) -> Option<Self> {
+        match repr {
+            0 => Some(LeaderboardMetric::StudyTime),
+            1 => Some(LeaderboardMetric::VideoTime),
+            2 => Some(LeaderboardMetric::Sessions),
+            3 => Some(LeaderboardMetric::Streak),
+            4 => Some(LeaderboardMetric::Balance),
+            _ => None
+        }
+    }
+
+    fn format_amount(self, amount: u64) -> String {
+        match self {
+            LeaderboardMetric::StudyTime | LeaderboardMetric::VideoTime =>
+                format_duration(Duration::from_secs(amount / 60 * 60)).to_string(),
+            LeaderboardMetric::Sessions => format!("{} sessions", amount),
+            LeaderboardMetric::Streak => format!("{} day streak", amount),
+            LeaderboardMetric::Balance => format!("{} coins", amount)
+        }
+    }
+}
+
+/// The whole streak leaderboard, best first.
+/// Streaks are not stored, so every user's is computed. Compute it once and reuse it where possible.
+async fn streak_ranking(pool: &SqlitePool, rules: &StudyRules) -> Vec<(UserId, u64)> {
+    let optouts = sqlx::query!("
+    SELECT uid
+    FROM leaderboard_optout
+    JOIN users ON user_id = users.id
+    ")
         .fetch_all(pool)
         .await
         .unwrap()
-        .iter()
-        .map(|r| (UserId::new(r.uid as u64), r.study_amount as u64))
-        .collect()
+        .into_iter()
+        .map(|r| UserId::new(r.uid as u64))
+        .collect::<HashSet<_>>();
+
+    let mut ranking = all_streaks(pool, rules).await
+        .into_iter()
+        .filter(|(user_id, streak)| streak.current > 0 && !optouts.contains(user_id))
+        .map(|(user_id, streak)| (user_id, streak.current as u64))
+        .collect::<Vec<_>>();
+
+    // Ties are ordered by user, so that pages and places agree.
+    ranking.sort_by_key(|&(user_id, amount)| (Reverse(amount), user_id));
+    ranking
+}
+
+/// Place of the user in the leaderboard, if they are in it.
+/// Users with the same amount are ordered like in [`fetch_leaderboard`].
+pub async fn user_place(ctx: &ActOnUser<'_>, rules: &StudyRules, metric: LeaderboardMetric, start: OffsetDateTime, end: OffsetDateTime) -> Option<u16> {
+    let uid = ctx.uid();
+    let min_length = (rules.streak_min_session_minutes * 60) as i64;
+    let include_manual = rules.manual_sessions_in_leaderboards;
+    let metric_repr = metric as u8;
+
+    let place = match metric {
+        LeaderboardMetric::StudyTime | LeaderboardMetric::VideoTime | LeaderboardMetric::Sessions => {
+            sqlx::query!(r#"
+            WITH totals AS (
+                SELECT
+                    users.id,
+                    users.uid,
+                    CASE $5
+                        WHEN 0 THEN SUM(length)
+                        WHEN 1 THEN SUM(video_length)
+                        ELSE SUM(length >= $3)
+                    END AS total
+                FROM users
+                JOIN study_sessions ON users.id = study_sessions.user_id
+                LEFT JOIN leaderboard_optout ON users.id = leaderboard_optout.user_id
+                WHERE
+                    leaderboard_optout.user_id IS NULL AND ended >= $1 AND ended < $2 AND
+                    ($4 OR source = 'voice')
+                GROUP BY users.id
+            ),
+            mine AS (SELECT id, total FROM totals WHERE uid = $6 AND total > 0)
+            SELECT (
+                SELECT COUNT(*) FROM totals
+                WHERE totals.total > mine.total OR (totals.total = mine.total AND totals.id < mine.id)
+            ) + 1 AS "place!: i64"
+            FROM mine
+            "#, start, end, min_length, include_manual, metric_repr, uid)
+                .fetch_optional(ctx.0)
+                .await
+                .unwrap()
+                .map(|r| r.place)
+        }
+        LeaderboardMetric::Balance => {
+            sqlx::query!(r#"
+            WITH totals AS (
+                SELECT users.id, users.uid, SUM(coins_diff) AS total
+                FROM users
+                JOIN coin_transactions ON users.id = coin_transactions.user_id
+                LEFT JOIN leaderboard_optout ON users.id = leaderboard_optout.user_id
+                WHERE leaderboard_optout.user_id IS NULL
+                GROUP BY users.id
+            ),
+            mine AS (SELECT id, total FROM totals WHERE uid = $1 AND total > 0)
+            SELECT (
+                SELECT COUNT(*) FROM totals
+                WHERE totals.total > mine.total OR (totals.total = mine.total AND totals.id < mine.id)
+            ) + 1 AS "place!: i64"
+            FROM mine
+            "#, uid)
+                .fetch_optional(ctx.0)
+                .await
+                .unwrap()
+                .map(|r| r.place)
+        }
+        LeaderboardMetric::Streak => {
+            streak_ranking(ctx.0, rules).await
+                .iter()
+                .position(|(user_id, _)| *user_id == ctx.1)
+                .map(|i| i as i64 + 1)
+        }
+    };
+
+    place.map(|p| p as u16)
+}
+
+/// Places of the leaderboard, best first, starting at offset.
+/// Streak and balance are always current, and do not depend on start and end.
+pub async fn fetch_leaderboard(pool: &SqlitePool, rules: &StudyRules, metric: LeaderboardMetric, start: OffsetDateTime, end: OffsetDateTime, limit: Option<i16>, offset: u32) -> Vec<(UserId, u64)> {
+    let min_length = (rules.streak_min_session_minutes * 60) as i64;
+    let include_manual = rules.manual_sessions_in_leaderboards;
+    let metric_repr = metric as u8;
+    let limit = limit.map(|l| l as i64).unwrap_or(-1);
+    let offset = offset as i64;
+
+    match metric {
+        LeaderboardMetric::StudyTime | LeaderboardMetric::VideoTime | LeaderboardMetric::Sessions => {
+            sqlx::query!(r#"
+            WITH totals AS (
+                SELECT
+                    users.id,
+                    users.uid,
+                    CASE $5
+                        WHEN 0 THEN SUM(length)
+                        WHEN 1 THEN SUM(video_length)
+                        ELSE SUM(length >= $3)
+                    END AS total
+                FROM users
+                JOIN study_sessions ON users.id = study_sessions.user_id
+                LEFT JOIN leaderboard_optout ON users.id = leaderboard_optout.user_id
+                WHERE
+                    leaderboard_optout.user_id IS NULL AND ended >= $1 AND ended < $2 AND
+                    ($4 OR source = 'voice')
+                GROUP BY users.id
+            )
+            SELECT uid AS "uid!: i64", total AS "total!: i64"
+            FROM totals
+            WHERE total > 0
+            ORDER BY total DESC, id
+            LIMIT $6 OFFSET $7
+            "#, start, end, min_length, include_manual, metric_repr, limit, offset)
+                .fetch_all(pool)
+                .await
+                .unwrap()
+                .iter()
+                .map(|r| (UserId::new(r.uid as u64), r.total as u64))
+                .collect()
+        }
+        LeaderboardMetric::Balance => {
+            sqlx::query!(r#"
+            WITH totals AS (
+                SELECT users.id, users.uid, SUM(coins_diff) AS total
+                FROM users
+                JOIN coin_transactions ON users.id = coin_transactions.user_id
+                LEFT JOIN leaderboard_optout ON users.id = leaderboard_optout.user_id
+                WHERE leaderboard_optout.user_id IS NULL
+                GROUP BY users.id
+            )
+            SELECT uid AS "uid!: i64", total AS "total!: i64"
+            FROM totals
+            WHERE total > 0
+            ORDER BY total DESC, id
+            LIMIT $1 OFFSET $2
+            "#, limit, offset)
+                .fetch_all(pool)
+                .await
+                .unwrap()
+                .iter()
+                .map(|r| (UserId::new(r.uid as u64), r.total as u64))
+                .collect()
+        }
+        LeaderboardMetric::Streak => {
+            streak_ranking(pool, rules).await
+                .into_iter()
+                .skip(offset as usize)
+                .take(if limit < 0 { usize::MAX } else { limit as usize })
+                .collect()
+        }
+    }
+}
+
+/// Content and navigation buttons for a page of the leaderboard between start and end, as seen by a user.
+/// Pages start at zero.
+pub async fn leaderboard_page(ctx: &ActOnUser<'_>, rules: &StudyRules, metric: LeaderboardMetric, start: OffsetDateTime, end: OffsetDateTime, page: u32) -> (String, Vec<CreateActionRow>) {
+    // One extra to know if there is a next page.
+    let (mut places, own_place) = if let LeaderboardMetric::Streak = metric {
+        // The page and the place come from the same ranking, since it is costly to compute.
+        let ranking = streak_ranking(ctx.0, rules).await;
+        let own_place = ranking
+            .iter()
+            .position(|(user_id, _)| *user_id == ctx.1)
+            .map(|i| i as u16 + 1);
+
+        (ranking
+            .into_iter()
+            .skip((page * PAGE_SIZE) as usize)
+            .take(PAGE_SIZE as usize + 1)
+            .collect::<Vec<_>>(), own_place)
+    } else {
+        (fetch_leaderboard(ctx.0, rules, metric, start, end, Some(PAGE_SIZE as i16 + 1), page * PAGE_SIZE).await,
+         user_place(ctx, rules, metric, start, end).await)
+    };
+    let has_next = places.len() > PAGE_SIZE as usize;
+    places.truncate(PAGE_SIZE as usize);
+
+    let mut b = MessageBuilder::new();
+
+    b.push_line(format!("## :trophy: Leaderboard: {}", metric.name()));
+    if matches!(metric, LeaderboardMetric::Streak | LeaderboardMetric::Balance) {
+        b.push_line("-# Right now");
+    } else if start == OffsetDateTime::UNIX_EPOCH {
+        b.push_line("-# All time");
+    } else {
+        b.push_line(format!("-# <t:{}:D> - <t:{}:D>",
+            start.unix_timestamp(),
+            end.unix_timestamp() - 1));
+    }
+
+    if places.is_empty() {
+        b.push_line("-# Nobody here yet.");
+    }
+
+    for (i, (user_id, amount)) in places.iter().enumerate() {
+        let place = page * PAGE_SIZE + i as u32 + 1;
+        let line = format!("**{}.** {} - {}",
+            place,
+            user_id.mention(),
+            metric.format_amount(*amount));
+
+        if *user_id == ctx.1 {
+            b.push_line(format!(":point_right: {}", line));
+        } else {
+            b.push_line(line);
+        }
+    }
+
+    b.push_line("");
+    b.push_line(own_place
+        .map(|p| format!("-# Your place: **{}**", p))
+        .unwrap_or("-# You are not in the leaderboard.".to_string()));
+
+    let (metric, start, end) = (metric as u8, start.unix_timestamp(), end.unix_timestamp());
+
+    let buttons = vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("leaderboard_page_{}_{}_{}_{}", page.saturating_sub(1), metric, start, end))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(format!("leaderboard_page_{}_{}_{}_{}", page + 1, metric, start, end))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(!has_next)
+    ])];
+
+    (b.build(), buttons)
 }
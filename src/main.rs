@@ -4,45 +4,173 @@ mod events;
 mod leaderboard;
 mod study;
 mod rewards;
+mod boosters;
+mod rollover;
+mod schedule;
+mod render;
+mod notifications;
+mod top_roles;
+mod streak;
+mod timezone;
+mod inventory;
+mod pomodoro;
+mod subjects;
+mod journal;
+mod goals;
+mod manual_sessions;
+mod schema;
 
 use core::panic;
-use std::collections::HashMap;
 use std::fs;
 use dotenv::dotenv;
 use events::event_handler;
 use prelude::create_user;
 use prelude::ActOnUser;
-use crate::study::StudyState;
+use crate::boosters::BoosterStacking;
+use crate::rewards::Reward;
+use crate::rollover::rollover_leaderboard;
+use crate::schedule::start_scheduler;
+use crate::study::{restore_study_states, touch_study_states, update_study_presence, StudyStates};
+use crate::pomodoro::Pomodoros;
 use log::{error, info};
-use poise::serenity_prelude::futures::lock::Mutex;
 use poise::serenity_prelude::CacheHttp;
-use poise::serenity_prelude::UserId;
 use poise::CreateReply;
 use poise::serenity_prelude as serenity;
 use serde::Deserialize;
 use sqlx::sqlite::SqlitePoolOptions;
-use std::{sync::Arc, time::Duration};
+use std::{num::{NonZeroU16, NonZeroU64}, sync::Arc, time::Duration};
 
 #[derive(Deserialize)]
 pub struct Config {
     study_earnings: StudyEarnings,
     channels: Channels,
+    #[serde(default)]
+    study_rules: StudyRules,
+    #[serde(default)]
+    leaderboard_rollover: LeaderboardRollover,
+    #[serde(default)]
+    notifications: Notifications,
+    /// Roles for the top of the monthly leaderboard, if any.
+    top_roles: Option<TopRoles>,
     star_cost: StarCost,
+    /// Coins per streak freeze, if they are for sale.
+    streak_freeze_cost: Option<u64>,
+    #[serde(default)]
+    pomodoro: PomodoroLengths,
     temp_charts_dir: String
 }
 
 #[derive(Deserialize)]
 pub struct StudyEarnings {
-    coins_per_minute: u64
+    coins_per_minute: u64,
+    /// How the multipliers of simultaneously active boosters are combined.
+    #[serde(default)]
+    booster_stacking: BoosterStacking
 }
 
 #[derive(Deserialize)]
 pub struct Channels {
     dm_backup_channel: u64,
     starboard_channel: u64,
+    /// Where monthly leaderboard winners are announced, if anywhere.
+    announcements_channel: Option<u64>,
+    /// Where moderators review manually logged sessions, if sessions can be logged.
+    log_review_channel: Option<u64>,
     slacking_voice_channels: Vec<u64>
 }
 
+/// Rules left out of the config take their defaults, which are off for most of them.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct StudyRules {
+    /// Whether self-deafening in a study voice channel counts as a break.
+    deafen_is_break: bool,
+    /// Minimum length of a session for its day to count towards the streak.
+    streak_min_session_minutes: u64,
+    /// Days in a row that can be skipped without losing the streak.
+    streak_grace_days: u16,
+    /// How long a user can be gone from voice and rejoin, and still continue the same session.
+    reconnect_grace_seconds: u64,
+    /// Whether manually logged sessions count towards the leaderboards.
+    manual_sessions_in_leaderboards: bool,
+    /// How many days back a session can be logged manually.
+    manual_session_max_age_days: u16,
+    /// Shortest daily or weekly goal that can be set.
+    /// Goals are never shorter than the minimum session length for the streak.
+    min_goal_minutes: u64
+}
+
+impl Default for StudyRules {
+    fn default() -> Self {
+        StudyRules {
+            deafen_is_break: false,
+            streak_min_session_minutes: 10,
+            streak_grace_days: 0,
+            reconnect_grace_seconds: 0,
+            manual_sessions_in_leaderboards: false,
+            manual_session_max_age_days: 7,
+            min_goal_minutes: 0
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct LeaderboardRollover {
+    /// Rewards for the top places of the monthly leaderboard, first place first.
+    winner_rewards: Vec<Reward>
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Notifications {
+    /// Minimum time between two leaderboard rank change notifications to the same user.
+    rank_change_cooldown_minutes: u64
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Notifications {
+            rank_change_cooldown_minutes: 60
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TopRoles {
+    guild: u64,
+    roles: Vec<TopRole>
+}
+
+#[derive(Deserialize)]
+pub struct TopRole {
+    role: u64,
+    /// Number of top places of the monthly leaderboard that get the role.
+    places: u16
+}
+
+/// Default lengths of Pomodoro phases.
+/// Lengths left out of the config take their defaults, and zero is rejected.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct PomodoroLengths {
+    work_minutes: NonZeroU64,
+    short_break_minutes: NonZeroU64,
+    long_break_minutes: NonZeroU64,
+    /// Number of work phases before a long break instead of a short one.
+    long_break_every: NonZeroU16
+}
+
+impl Default for PomodoroLengths {
+    fn default() -> Self {
+        PomodoroLengths {
+            work_minutes: NonZeroU64::new(25).unwrap(),
+            short_break_minutes: NonZeroU64::new(5).unwrap(),
+            long_break_minutes: NonZeroU64::new(15).unwrap(),
+            long_break_every: NonZeroU16::new(4).unwrap()
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct StarCost {
     base: u64,
@@ -51,10 +179,12 @@ pub struct StarCost {
 }
 
 
+#[derive(Clone)]
 pub struct Data {
-    config: Config,
+    config: Arc<Config>,
     db_pool: sqlx::SqlitePool,
-    study_states: Mutex<HashMap<UserId, StudyState>>
+    study_states: StudyStates,
+    pomodoros: Pomodoros
 }
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -103,12 +233,30 @@ async fn main() -> Result<(), sqlx::Error> {
             .expect("Missing SQLITE_CONNSTR environment variable"))
         .await?;
 
+    schema::add_missing_columns(&db_pool).await?;
+
     let options = poise::FrameworkOptions {
         commands: vec![
             commands::stats::stats(),
             commands::star::star(),
             commands::simulate_study_session::simulate_study_session(),
-            commands::results::results()
+            commands::results::results(),
+            commands::take_break::take_break(),
+            commands::boosters::boosters(),
+            commands::leaderboard::leaderboard(),
+            commands::privacy::privacy(),
+            commands::leaderboard_archive::leaderboard_archive(),
+            commands::leaderboard_image::leaderboard_image(),
+            commands::notifications::notifications(),
+            commands::timezone::timezone(),
+            commands::buy_freeze::buy_freeze(),
+            commands::session::session(),
+            commands::studying::studying(),
+            commands::pomodoro::pomodoro(),
+            commands::subject::subject(),
+            commands::journal::journal(),
+            commands::goal::goal(),
+            commands::log::log()
         ],
 
         prefix_options: poise::PrefixFrameworkOptions {
@@ -162,15 +310,43 @@ async fn main() -> Result<(), sqlx::Error> {
 
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
 
-                ctx.set_presence(
-                    Some(serenity::ActivityData::watching("you")),
-                    serenity::OnlineStatus::Idle,
-                );
+                info!("Restoring study sessions.");
+
+                let study_states = restore_study_states(&db_pool).await;
+
+                info!("Restored {} study sessions.", study_states.len());
+
+                {
+                    let (ctx, db_pool) = (ctx.clone(), db_pool.clone());
+                    tokio::spawn(async move {
+                        // Not touched right away, so that sessions that ended while the bot was offline
+                        // are closed at the time they were last seen, once every guild is received.
+                        let period = Duration::from_secs(60);
+                        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+                        loop {
+                            interval.tick().await;
+                            touch_study_states(&db_pool).await;
+                            update_study_presence(&ctx, &db_pool).await;
+                        }
+                    });
+                }
+
+                let config = Arc::new(config);
+
+                info!("Starting scheduler.");
+
+                start_scheduler(ctx.clone(), db_pool.clone(), config.clone()).await?;
+
+                // Catch up in case the bot was offline when the month ended.
+                if let Err(e) = rollover_leaderboard(ctx, &db_pool, &config).await {
+                    error!("Leaderboard rollover failed: {:?}", e);
+                }
 
                 Ok(Data {
                     config,
                     db_pool,
-                    study_states: HashMap::new().into()
+                    study_states: Arc::new(study_states.into()),
+                    pomodoros: Pomodoros::default()
                 })
             })
         })
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use humantime::format_duration;
+use poise::serenity_prelude::{ButtonStyle, CreateAllowedMentions, CreateButton, CreateMessage, Mentionable, MessageBuilder, UserId};
+use sqlx::{types::time::OffsetDateTime, SqliteConnection};
+
+use crate::{journal::write_session_note_in, prelude::ActOnUser, study::base_coins, StudyEarnings};
+
+pub struct Submission {
+    pub id: i64,
+    pub user_id: UserId,
+    pub started: OffsetDateTime,
+    pub length: Duration,
+    pub note: Option<String>
+}
+
+/// Queues a manually logged session for review.
+pub async fn submit_session(ctx: &ActOnUser<'_>, started: OffsetDateTime, length: Duration, note: Option<&str>) -> Submission {
+    let uid = ctx.uid();
+    let length_secs = length.as_secs() as i64;
+
+    let id = sqlx::query!("
+    INSERT INTO session_submissions (user_id, started, length, note)
+    SELECT id, $2, $3, $4 FROM users WHERE uid = $1
+    ", uid, started, length_secs, note)
+        .execute(ctx.0)
+        .await.unwrap()
+        .last_insert_rowid();
+
+    Submission {
+        id,
+        user_id: ctx.1,
+        started,
+        length,
+        note: note.map(str::to_string)
+    }
+}
+
+/// Sessions and pending submissions of a user that overlap a time range, as (start, end), earliest first.
+/// The session the user is in, if studying_since is its start, counts up to now.
+pub async fn overlapping_sessions(ctx: &ActOnUser<'_>, studying_since: Option<OffsetDateTime>, start: OffsetDateTime, end: OffsetDateTime) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+    let mut conn = ctx.0.acquire().await.unwrap();
+    overlapping_sessions_in(&mut conn, ctx.uid(), studying_since, start, end).await.unwrap()
+}
+
+pub async fn overlapping_sessions_in(conn: &mut SqliteConnection, uid: i64, studying_since: Option<OffsetDateTime>, start: OffsetDateTime, end: OffsetDateTime) -> Result<Vec<(OffsetDateTime, OffsetDateTime)>, sqlx::Error> {
+    let sessions = sqlx::query!(r#"
+    SELECT
+        started AS "started: OffsetDateTime",
+        ended AS "ended: OffsetDateTime",
+        length
+    FROM study_sessions
+    JOIN users ON user_id = users.id
+    WHERE uid = $1 AND ended > $2
+    "#, uid, start)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|r| (r.started.unwrap_or(r.ended - time::Duration::seconds(r.length)), r.ended));
+
+    let submissions = sqlx::query!(r#"
+    SELECT started AS "started: OffsetDateTime", length
+    FROM session_submissions
+    JOIN users ON user_id = users.id
+    WHERE uid = $1
+    "#, uid)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|r| (r.started, r.started + time::Duration::seconds(r.length)));
+
+    let active = studying_since.map(|s| (s, OffsetDateTime::now_utc()));
+
+    let mut overlapping = sessions
+        .chain(submissions)
+        .chain(active)
+        .filter(|&(s, e)| s < end && e > start)
+        .collect::<Vec<_>>();
+    overlapping.sort();
+
+    Ok(overlapping)
+}
+
+/// Lists time ranges, one per line, e.g. "<t:..> → <t:..>".
+pub fn describe_time_ranges(ranges: &[(OffsetDateTime, OffsetDateTime)]) -> String {
+    let mut b = MessageBuilder::new();
+
+    for (start, end) in ranges {
+        b.push_line(format!("- <t:{}:f> → <t:{}:t>", start.unix_timestamp(), end.unix_timestamp()));
+    }
+
+    b.build()
+}
+
+/// Removes a submission from the review queue.
+/// None if it does not exist, e.g. because another moderator already reviewed it.
+/// Meant to be part of a transaction with [`approve_submission`].
+pub async fn take_submission(conn: &mut SqliteConnection, id: i64) -> Result<Option<Submission>, sqlx::Error> {
+    let Some(submission) = sqlx::query!(r#"
+    SELECT uid, started AS "started: OffsetDateTime", length, note
+    FROM session_submissions
+    JOIN users ON user_id = users.id
+    WHERE session_submissions.id = $1
+    "#, id)
+        .fetch_optional(&mut *conn)
+        .await? else { return Ok(None) };
+
+    let deleted = sqlx::query!("DELETE FROM session_submissions WHERE id = $1", id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    Ok((deleted > 0).then(|| Submission {
+        id,
+        user_id: UserId::new(submission.uid as u64),
+        started: submission.started,
+        length: Duration::from_secs(submission.length as u64),
+        note: submission.note
+    }))
+}
+
+/// Saves an approved submission as a study session and pays its coins.
+/// Returns the session ID and the coins earned.
+pub async fn approve_submission(conn: &mut SqliteConnection, earnings: &StudyEarnings, submission: &Submission) -> Result<(i64, u64), sqlx::Error> {
+    let uid = i64::from(submission.user_id);
+
+    // Boosters only apply to time tracked in voice.
+    let coins = base_coins(earnings, submission.length, Duration::ZERO);
+
+    let session_id = {
+        let coins = coins as i64;
+
+        let coin_reward_id = sqlx::query!("
+        INSERT INTO coin_transactions (user_id, coins_diff)
+        SELECT users.id, $2 FROM users WHERE uid = $1
+        ", uid, coins)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+
+        let length = submission.length.as_secs() as i64;
+        let started = submission.started;
+        let ended = submission.started + submission.length;
+
+        sqlx::query!("
+        INSERT INTO study_sessions (user_id, coin_reward_id, length, video_length, started, ended, source)
+        SELECT users.id, $2, $3, 0, $4, $5, 'manual' FROM users WHERE uid = $1
+        ", uid, coin_reward_id, length, started, ended)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid()
+    };
+
+    if let Some(note) = &submission.note {
+        write_session_note_in(conn, session_id, note).await?;
+    }
+
+    Ok((session_id, coins))
+}
+
+/// Describes a submission, e.g. "@user studied **2h** <t:..> → <t:..>".
+pub fn describe_submission(submission: &Submission) -> String {
+    let mut b = MessageBuilder::new();
+
+    b.push(submission.user_id.mention().to_string());
+    b.push(" studied ");
+    b.push_bold(format_duration(submission.length).to_string());
+    b.push_line(format!(": <t:{}:f> → <t:{}:t>",
+        submission.started.unix_timestamp(),
+        (submission.started + submission.length).unix_timestamp()));
+
+    if let Some(note) = &submission.note {
+        b.push_quote_line_safe(note);
+    }
+
+    b.build()
+}
+
+/// Message in the review queue, with buttons for moderators to approve or reject the submission.
+pub fn review_message(submission: &Submission) -> CreateMessage {
+    CreateMessage::new()
+        .content(
+            MessageBuilder::new()
+            .push_line("## :pencil: Logged session")
+            .push(describe_submission(submission))
+            .push("-# Submission ID: ")
+            .push_mono_line(submission.id.to_string())
+            .build())
+        .button(
+            CreateButton::new(format!("approve_log_{}", submission.id))
+            .label("Approve")
+            .style(ButtonStyle::Success))
+        .button(
+            CreateButton::new(format!("reject_log_{}", submission.id))
+            .label("Reject")
+            .style(ButtonStyle::Danger))
+        .allowed_mentions(CreateAllowedMentions::new())
+}
@@ -0,0 +1,85 @@
+use poise::serenity_prelude::{CacheHttp, Context, CreateMessage, MessageBuilder, User, UserId};
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{leaderboard::{fetch_leaderboard, LeaderboardMetric}, prelude::{try_dm_or_in_guild, ActOnUser}, Data};
+
+/// Number of top places that rank change notifications are sent for.
+pub const NOTIFIED_PLACES: i16 = 10;
+
+pub async fn user_rank_notifications(ctx: &ActOnUser<'_>) -> bool {
+    let uid = ctx.uid();
+
+    sqlx::query!("
+    SELECT user_id FROM rank_change_notifications
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ", uid)
+        .fetch_optional(ctx.0)
+        .await
+        .unwrap()
+        .is_some()
+}
+
+/// Takes the user's rank change notification slot, if they are opted in and not rate limited.
+/// Returns whether the user may be notified.
+async fn claim_rank_notification(ctx: &ActOnUser<'_>, cooldown_minutes: u64) -> bool {
+    let uid = ctx.uid();
+    let cooldown = cooldown_minutes as i64 * 60;
+
+    sqlx::query!("
+    UPDATE rank_change_notifications
+    SET last_notified = UNIXEPOCH()
+    WHERE
+        user_id IN (SELECT id FROM users WHERE uid = $1) AND
+        (last_notified IS NULL OR last_notified <= UNIXEPOCH() - $2)
+    ", uid, cooldown)
+        .execute(ctx.0)
+        .await
+        .unwrap()
+        .rows_affected() != 0
+}
+
+/// Notifies members who were passed by the overtaker on the leaderboard between start and end,
+/// and lost one of their top places because of it.
+/// `top_before` is the top of the leaderboard before the overtaker's session was saved.
+pub async fn notify_overtaken(ctx: &Context, data: &Data, overtaker: &User, top_before: &[(UserId, u64)], start: OffsetDateTime, end: OffsetDateTime) {
+    let places_after = fetch_leaderboard(&data.db_pool, &data.config.study_rules, LeaderboardMetric::StudyTime, start, end, None, 0).await;
+    let place_of = |places: &[(UserId, u64)], user_id: UserId| places
+        .iter()
+        .position(|(id, _)| *id == user_id);
+
+    let Some(overtaker_after) = place_of(&places_after, overtaker.id) else { return };
+    let overtaker_before = place_of(top_before, overtaker.id);
+
+    for (place_before, (user_id, _)) in top_before.iter().enumerate() {
+        if *user_id == overtaker.id || overtaker_before.is_some_and(|p| p < place_before) {
+            continue
+        }
+
+        let Some(place_after) = place_of(&places_after, *user_id) else { continue };
+
+        if place_after <= place_before || overtaker_after > place_after {
+            continue
+        }
+
+        let act_on_user_ctx = &ActOnUser(&data.db_pool, *user_id);
+        if !claim_rank_notification(act_on_user_ctx, data.config.notifications.rank_change_cooldown_minutes).await {
+            continue
+        }
+
+        let Ok(user) = ctx.http().get_user(*user_id).await else { continue };
+
+        try_dm_or_in_guild(ctx, data, ctx.http(), &user, CreateMessage::new()
+            .content(
+                MessageBuilder::new()
+                .push(":chart_with_downwards_trend: ")
+                .push_bold_safe(overtaker.global_name.as_ref().unwrap_or(&overtaker.name))
+                .push(" just passed you on the leaderboard! You are now ")
+                .push_bold(format!("#{}", place_after + 1))
+                .push(" (was ")
+                .push_bold(format!("#{}", place_before + 1))
+                .push_line(").")
+                .push("-# Turn these off with ")
+                .push_mono_line("/notifications leaderboard")
+                .build())).await;
+    }
+}
@@ -0,0 +1,163 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use log::{error, info};
+use poise::serenity_prelude::{futures::lock::Mutex, ChannelId, Context, CreateMessage, GuildId, UserId};
+use sqlx::{types::time::OffsetDateTime, SqlitePool};
+use tokio::task::AbortHandle;
+
+use crate::{study::{set_on_pomodoro_break, StudyStates}, Data, PomodoroLengths};
+
+/// Who a Pomodoro cycle is run for.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PomodoroTarget {
+    /// A single studying user, announced in DMs.
+    User(UserId),
+    /// Everyone studying in a voice channel, announced in its text chat.
+    Channel(GuildId, ChannelId)
+}
+
+/// Running Pomodoro cycles.
+pub type Pomodoros = Arc<Mutex<HashMap<PomodoroTarget, AbortHandle>>>;
+
+#[derive(Clone, Copy)]
+enum Phase {
+    Work,
+    ShortBreak,
+    LongBreak
+}
+
+impl Phase {
+    fn is_break(self) -> bool {
+        !matches!(self, Phase::Work)
+    }
+
+    fn length(self, lengths: &PomodoroLengths) -> Duration {
+        Duration::from_secs(60 * match self {
+            Phase::Work => lengths.work_minutes,
+            Phase::ShortBreak => lengths.short_break_minutes,
+            Phase::LongBreak => lengths.long_break_minutes
+        }.get())
+    }
+
+    fn announcement(self, length: Duration, ends: OffsetDateTime) -> String {
+        let (emoji, name) = match self {
+            Phase::Work => (":tomato:", "Focus"),
+            Phase::ShortBreak => (":coffee:", "Short break"),
+            Phase::LongBreak => (":beach:", "Long break")
+        };
+
+        format!("{} **{}** for {} minutes, until <t:{}:t> (<t:{}:R>).",
+            emoji,
+            name,
+            length.as_secs() / 60,
+            ends.unix_timestamp(),
+            ends.unix_timestamp())
+    }
+}
+
+impl PomodoroTarget {
+    /// Studying users the cycle applies to right now.
+    pub async fn users(self, ctx: &Context, study_states: &StudyStates) -> Vec<UserId> {
+        let candidates = match self {
+            PomodoroTarget::User(user_id) => vec![user_id],
+            PomodoroTarget::Channel(guild_id, channel_id) => ctx.cache
+                .guild(guild_id)
+                .map(|guild| guild.voice_states
+                    .values()
+                    .filter(|vs| vs.channel_id == Some(channel_id))
+                    .map(|vs| vs.user_id)
+                    .collect())
+                .unwrap_or_default()
+        };
+
+        let study_states = study_states.lock().await;
+        candidates
+            .into_iter()
+            .filter(|user_id| study_states.contains_key(user_id))
+            .collect()
+    }
+
+    async fn announce(self, ctx: &Context, content: String) {
+        let message = CreateMessage::new().content(content);
+        let sent = match self {
+            PomodoroTarget::User(user_id) => user_id.direct_message(ctx, message).await,
+            PomodoroTarget::Channel(_, channel_id) => channel_id.send_message(ctx, message).await
+        };
+
+        if let Err(e) = sent {
+            error!("Failed to announce Pomodoro phase: {:?}", e);
+        }
+    }
+}
+
+/// Starts a Pomodoro cycle for the target.
+/// Returns false if one is already running for it.
+pub async fn start_pomodoro(ctx: &Context, data: &Data, target: PomodoroTarget, lengths: PomodoroLengths) -> bool {
+    let mut pomodoros = data.pomodoros.lock().await;
+
+    if pomodoros.contains_key(&target) {
+        return false
+    }
+
+    let task = tokio::spawn(run_pomodoro(
+        ctx.clone(),
+        data.study_states.clone(),
+        data.db_pool.clone(),
+        data.pomodoros.clone(),
+        target,
+        lengths));
+
+    pomodoros.insert(target, task.abort_handle());
+    true
+}
+
+/// Stops the target's Pomodoro cycle, ending its break if it was on one.
+/// Returns false if none was running.
+pub async fn stop_pomodoro(ctx: &Context, data: &Data, target: PomodoroTarget) -> bool {
+    let Some(task) = data.pomodoros.lock().await.remove(&target) else { return false };
+    task.abort();
+
+    for user_id in target.users(ctx, &data.study_states).await {
+        set_on_pomodoro_break(&data.study_states, &data.db_pool, user_id, false).await;
+    }
+
+    true
+}
+
+/// Cycles through the phases until stopped, or until nobody it applies to is studying.
+async fn run_pomodoro(ctx: Context, study_states: StudyStates, db_pool: SqlitePool, pomodoros: Pomodoros, target: PomodoroTarget, lengths: PomodoroLengths) {
+    let mut phase = Phase::Work;
+    let mut work_phases = 0;
+
+    loop {
+        let users = target.users(&ctx, &study_states).await;
+        if users.is_empty() {
+            break
+        }
+
+        for user_id in users {
+            set_on_pomodoro_break(&study_states, &db_pool, user_id, phase.is_break()).await;
+        }
+
+        let length = phase.length(&lengths);
+        target.announce(&ctx, phase.announcement(length, OffsetDateTime::now_utc() + length)).await;
+        tokio::time::sleep(length).await;
+
+        phase = match phase {
+            Phase::Work => {
+                work_phases += 1;
+                if work_phases % lengths.long_break_every.get() == 0 {
+                    Phase::LongBreak
+                } else {
+                    Phase::ShortBreak
+                }
+            }
+            _ => Phase::Work
+        };
+    }
+
+    info!("Pomodoro ended, nobody is studying");
+
+    pomodoros.lock().await.remove(&target);
+    target.announce(&ctx, ":tomato: Pomodoro stopped, since nobody is studying.".to_string()).await;
+}
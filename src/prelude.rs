@@ -1,7 +1,7 @@
 use core::fmt;
 use std::{error::Error, time::Duration};
 
-use sqlx::SqlitePool;
+use sqlx::{types::time::OffsetDateTime, SqliteExecutor, SqlitePool};
 use poise::serenity_prelude::{self as serenity, CacheHttp, ChannelId, Context, CreateButton, CreateMessage, Mentionable, Message, User, UserId};
 
 use crate::Data;
@@ -20,6 +20,15 @@ pub async fn create_message_ref(pool: &SqlitePool, message: &serenity::Message)
         .last_insert_rowid()
 }
 
+/// Time passed from `from` to `to`, or zero if `to` is before `from`.
+pub fn elapsed_between(from: OffsetDateTime, to: OffsetDateTime) -> Duration {
+    (to - from).try_into().unwrap_or(Duration::ZERO)
+}
+
+/// Pool and user that a database function acts on.
+/// Functions that take it panic on database errors. Their `_in` variants, e.g. [`coin_transaction_in`],
+/// take a connection or transaction and the user's ID instead, so that several of them can be committed
+/// together, and return the errors so that the caller can roll back.
 pub struct ActOnUser<'a>(pub &'a SqlitePool, pub UserId);
 
 impl ActOnUser<'_> {
@@ -43,9 +52,11 @@ pub async fn user_balance<'a>(ctx: &ActOnUser<'a>) -> u64 {
 }
 
 pub async fn coin_transaction<'a>(ctx: &ActOnUser<'a>, balance_diff: i64) -> bool {
-    let uid = ctx.uid();
+    coin_transaction_in(ctx.0, ctx.uid(), balance_diff).await.unwrap()
+}
 
-    sqlx::query!("
+pub async fn coin_transaction_in(executor: impl SqliteExecutor<'_>, uid: i64, balance_diff: i64) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query!("
     WITH user AS (
             SELECT users.id, COALESCE(SUM(coins_diff), 0) AS balance FROM users
             JOIN coin_transactions ON users.id = coin_transactions.user_id
@@ -54,14 +65,9 @@ pub async fn coin_transaction<'a>(ctx: &ActOnUser<'a>, balance_diff: i64) -> boo
     INSERT INTO coin_transactions (user_id, coins_diff)
     SELECT id, $2 FROM user WHERE balance + $2 >= 0
     ", uid, balance_diff)
-        .execute(ctx.0)
-        .await
-        .unwrap()
-        .rows_affected() != 0
-}
-
-pub async fn add_coins(ctx: &ActOnUser<'_>, coins: u64) -> bool {
-    coin_transaction(ctx, coins as i64).await
+        .execute(executor)
+        .await?
+        .rows_affected() != 0)
 }
 
 pub async fn sub_coins(ctx: &ActOnUser<'_>, coins: u64) -> bool {
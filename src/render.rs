@@ -0,0 +1,28 @@
+use std::{collections::HashMap, sync::Arc};
+
+use resvg::{tiny_skia::Pixmap, usvg::{ImageHrefResolver, ImageKind, Options, Transform, Tree}};
+
+use crate::Error;
+
+/// Renders an SVG document to PNG.
+/// `images` maps the `href` of `<image>` elements to their data.
+pub fn render_png(svg: &str, images: HashMap<String, ImageKind>) -> Result<Vec<u8>, Error> {
+    let mut font_db = resvg::usvg::fontdb::Database::new();
+    font_db.load_system_fonts();
+
+    let options = Options {
+        fontdb: Arc::new(font_db),
+        image_href_resolver: ImageHrefResolver {
+            resolve_string: Box::new(move |href, _| images.get(href).cloned()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let rtree = Tree::from_str(svg, &options)?;
+
+    let size = rtree.size();
+    let mut pixmap = Pixmap::new(size.width() as u32, size.height() as u32).unwrap();
+    resvg::render(&rtree, Transform::identity(), &mut pixmap.as_mut());
+
+    Ok(pixmap.encode_png()?)
+}
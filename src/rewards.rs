@@ -3,16 +3,47 @@ use std::time::Duration;
 use humantime::format_duration;
 use poise::serenity_prelude::CreateMessage;
 use rand::Rng;
+use serde::{Deserialize, Deserializer};
 
-use crate::prelude::{add_coins, ActOnUser};
+use sqlx::SqliteConnection;
 
-#[derive(Clone, Copy)]
+use crate::{inventory::{give_item_in, Item}, prelude::{coin_transaction_in, ActOnUser}};
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Reward {
     Coins(u64),
-    Booster { multiplier: u16, expiration: Duration }
+    Booster {
+        multiplier: u16,
+        #[serde(deserialize_with = "deserialize_duration")]
+        expiration: Duration
+    },
+    /// Number of streak freezes.
+    #[serde(rename = "streak_freeze")]
+    StreakFreeze(u64)
+}
+
+/// Deserializes a humantime duration string, e.g. "3days".
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    humantime::parse_duration(&s).map_err(serde::de::Error::custom)
 }
 
 impl Reward {
+    pub fn description(&self) -> String {
+        match *self {
+            Reward::Coins(amount) =>
+                format!("+{} coins", amount),
+            Reward::Booster { multiplier, expiration } =>
+                format!(
+                    "{}x booster (expires in {})",
+                    multiplier as f64 / 100.0,
+                    format_duration(expiration)),
+            Reward::StreakFreeze(amount) =>
+                format!("+{} streak freeze{}", amount, if amount == 1 { "" } else { "s" })
+        }
+    }
+
     /*pub fn to_message(self) -> CreateMessage {
         let (emoji, description) = match self {
             Reward::Coins(amount) =>
@@ -32,46 +63,49 @@ impl Reward {
         let mut rng = rand::thread_rng();
         match rng.gen_range(0..100) {
             0..70 => Self::Coins(rng.gen_range(1..8) * 100),
-            70..99 => Self::Booster {
+            70..95 => Self::Booster {
                 multiplier: rng.gen_range(15..25) * 10,
                 expiration: Duration::from_secs(rng.gen_range(1..24 * 8) * 60 * 60)
             },
-            _ => unreachable!()
+            _ => Self::StreakFreeze(1)
         }
     }
 }
 
 pub async fn user_claim_reward(ctx: &ActOnUser<'_>, reward: Reward, reason: String) -> i64 {
-    let uid = ctx.uid();
-    let description = match reward {
-        Reward::Coins(amount) => {
-            add_coins(ctx, amount).await;
-            format!("+{} coins", amount)
-        }
-        Reward::Booster { multiplier, expiration } => {
-            {
-                let multiplier = multiplier as i64;
-                let expiration = expiration.as_secs() as i64;
-                sqlx::query!("
-                INSERT INTO boosters
-                VALUES (NULL, (SELECT id FROM users WHERE uid = $1), $2, UNIXEPOCH('now') + $3)
-                ", uid, multiplier, expiration)
-                    .execute(ctx.0)
-                    .await.unwrap();
-            }
+    let mut conn = ctx.0.acquire().await.unwrap();
+    claim_reward_in(&mut conn, ctx.uid(), reward, reason).await.unwrap()
+}
 
-            format!(
-                "{}x booster (expires in {})",
-                multiplier as f64 / 100.0,
-                format_duration(expiration))
-        }
-    };
+pub async fn claim_reward_in(conn: &mut SqliteConnection, uid: i64, reward: Reward, reason: String) -> Result<i64, sqlx::Error> {
+    let description = reward.description();
 
-    sqlx::query!("
+    if let Reward::Coins(amount) = reward {
+        coin_transaction_in(&mut *conn, uid, amount as i64).await?;
+    }
+
+    let reward_id = sqlx::query!("
     INSERT INTO rewards (user_id, description, reason)
     VALUES ((SELECT id FROM users WHERE uid = $1), $2, $3)
     ", uid, description, reason)
-        .execute(ctx.0)
-        .await.unwrap()
-        .last_insert_rowid()
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+    if let Reward::Booster { multiplier, expiration } = reward {
+        let multiplier = multiplier as i64;
+        let expiration = expiration.as_secs() as i64;
+        sqlx::query!("
+        INSERT INTO boosters (user_id, reward_id, multiplier, expiration)
+        VALUES ((SELECT id FROM users WHERE uid = $1), $2, $3, UNIXEPOCH('now') + $4)
+        ", uid, reward_id, multiplier, expiration)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    if let Reward::StreakFreeze(amount) = reward {
+        give_item_in(&mut *conn, uid, Item::StreakFreeze, amount).await?;
+    }
+
+    Ok(reward_id)
 }
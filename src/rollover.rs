@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use humantime::format_duration;
+use log::info;
+use poise::serenity_prelude::{CacheHttp, ChannelId, Context, CreateMessage, Mentionable, MessageBuilder};
+use sqlx::{types::time::OffsetDateTime, SqlitePool};
+
+use crate::{leaderboard::{fetch_leaderboard, LeaderboardMetric, LeaderboardPeriod}, rewards::claim_reward_in, Config, Error};
+
+/// Month key used in the leaderboard archive, e.g. 2024-11.
+pub fn archive_month(date: OffsetDateTime) -> String {
+    format!("{}-{:02}", date.year(), date.month() as u8)
+}
+
+/// Archives the leaderboard of the previous month, rewards its winners and announces them.
+/// Does nothing if the month has already been archived.
+pub async fn rollover_leaderboard(ctx: &Context, pool: &SqlitePool, config: &Config) -> Result<(), Error> {
+    let this_month_start = LeaderboardPeriod::Month.bounds().0;
+    let (start, end) = LeaderboardPeriod::Month.bounds_at(this_month_start.date().previous_day().unwrap());
+    let month = archive_month(start);
+
+    let archived = sqlx::query!("
+    SELECT COUNT(*) AS count FROM leaderboard_archive
+    WHERE month = $1
+    ", month)
+        .fetch_one(pool)
+        .await?
+        .count != 0;
+
+    if archived {
+        return Ok(())
+    }
+
+    let places = fetch_leaderboard(pool, &config.study_rules, LeaderboardMetric::StudyTime, start, end, None, 0).await;
+
+    if places.is_empty() {
+        return Ok(())
+    }
+
+    // The archive marks the month as done, so it is only saved together with the rewards.
+    let mut tx = pool.begin().await?;
+
+    for (i, (user_id, study_amount)) in places.iter().enumerate() {
+        let uid = i64::from(*user_id);
+        let place = i as i64 + 1;
+        let study_amount = *study_amount as i64;
+
+        let inserted = sqlx::query!("
+        INSERT OR IGNORE INTO leaderboard_archive (user_id, month, place, study_amount)
+        SELECT id, $2, $3, $4 FROM users WHERE uid = $1
+        ", uid, month, place, study_amount)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() != 0;
+
+        // Archiving the first place claims the month, so that a rollover running
+        // at the same time (e.g. the startup catch-up) does not reward it again.
+        if i == 0 && !inserted {
+            return Ok(())
+        }
+    }
+
+    info!("Archived leaderboard of {}", month);
+
+    let rewards = &config.leaderboard_rollover.winner_rewards;
+
+    for (i, ((user_id, _), reward)) in places.iter().zip(rewards).enumerate() {
+        claim_reward_in(
+            &mut tx,
+            i64::from(*user_id),
+            *reward,
+            format!("#{} on the {} leaderboard", i + 1, start.month())).await?;
+    }
+
+    tx.commit().await?;
+
+    let Some(announcements_channel) = config.channels.announcements_channel else { return Ok(()) };
+
+    let mut b = MessageBuilder::new();
+    b.push_line(format!("# :trophy: {} {} leaderboard", start.month(), start.year()));
+
+    for (i, (user_id, study_amount)) in places.iter().take(rewards.len().max(3)).enumerate() {
+        match i {
+            0 => b.push(":first_place: "),
+            1 => b.push(":second_place: "),
+            2 => b.push(":third_place: "),
+            _ => b.push(format!("**{}.** ", i + 1))
+        };
+        b.push(user_id.mention().to_string());
+        b.push(" - ");
+        b.push_bold(format_duration(Duration::from_secs(study_amount / 60 * 60)).to_string());
+
+        if let Some(reward) = rewards.get(i) {
+            b.push(format!(" ({})", reward.description()));
+        }
+
+        b.push_line("");
+    }
+
+    b.push_line(format!("-# Thank you all for studying with us! {} members made it onto the leaderboard.", places.len()));
+
+    ChannelId::new(announcements_channel)
+        .send_message(ctx.http(), CreateMessage::new().content(b.build()))
+        .await?;
+
+    Ok(())
+}
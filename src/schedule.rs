@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use log::error;
+use poise::serenity_prelude::Context;
+use sqlx::SqlitePool;
+use tokio_cron_scheduler::{Job, JobScheduler, JobSchedulerError};
+
+use crate::{rollover::rollover_leaderboard, top_roles::sync_top_roles, Config};
+
+/// Starts the scheduled jobs.
+pub async fn start_scheduler(ctx: Context, db_pool: SqlitePool, config: Arc<Config>) -> Result<JobScheduler, JobSchedulerError> {
+    let scheduler = JobScheduler::new().await?;
+
+    // Shortly after midnight on the first of every month.
+    scheduler.add({
+        let (ctx, db_pool, config) = (ctx.clone(), db_pool.clone(), config.clone());
+        Job::new_async("5 0 0 1 * *", move |_, _| {
+            let (ctx, db_pool, config) = (ctx.clone(), db_pool.clone(), config.clone());
+            Box::pin(async move {
+                if let Err(e) = rollover_leaderboard(&ctx, &db_pool, &config).await {
+                    error!("Leaderboard rollover failed: {:?}", e);
+                }
+            })
+        })?
+    }).await?;
+
+    // Every 15 minutes, which also catches the leaderboard reset at the start of the month.
+    scheduler.add(Job::new_async("30 */15 * * * *", move |_, _| {
+        let (ctx, db_pool, config) = (ctx.clone(), db_pool.clone(), config.clone());
+        Box::pin(async move {
+            if let Err(e) = sync_top_roles(&ctx, &db_pool, &config).await {
+                error!("Top role sync failed: {:?}", e);
+            }
+        })
+    })?).await?;
+
+    scheduler.start().await?;
+
+    Ok(scheduler)
+}
@@ -0,0 +1,35 @@
+use log::info;
+use sqlx::SqlitePool;
+
+/// Columns added to tables after they were first created, as (table, column, definition).
+/// `CREATE TABLE IF NOT EXISTS` leaves existing tables untouched, so these are added to older databases at startup.
+/// SQLite cannot add columns with non-constant defaults, so the definitions may differ from migrations/tables.sql.
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("study_sessions", "break_length", "INTEGER NOT NULL DEFAULT(0) CHECK(break_length <= length)"),
+    ("study_sessions", "started", "INTEGER NULL"),
+    ("study_sessions", "source", "TEXT NOT NULL DEFAULT('voice') CHECK(source IN ('voice', 'manual'))"),
+    ("boosters", "reward_id", "INTEGER NULL REFERENCES rewards (id) ON DELETE SET NULL"),
+    // Boosters from before activation times were recorded count as always active.
+    ("boosters", "activated", "INTEGER NOT NULL DEFAULT(0)")
+];
+
+/// Adds the columns of [`ADDED_COLUMNS`] that the database is missing.
+pub async fn add_missing_columns(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    for (table, column, definition) in ADDED_COLUMNS {
+        let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM pragma_table_info($1) WHERE name = $2")
+            .bind(table)
+            .bind(column)
+            .fetch_one(pool)
+            .await? > 0;
+
+        if !exists {
+            info!("Adding column {}.{}", table, column);
+
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+
+use chrono_tz::Tz;
+use poise::serenity_prelude::UserId;
+use sqlx::{types::time::{Date, OffsetDateTime}, SqlitePool};
+
+use crate::{inventory::{use_item, Item}, prelude::ActOnUser, timezone::{local_date, split_across_days, user_timezone}, StudyRules};
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Streak {
+    /// Days studied in the ongoing streak.
+    pub current: u16,
+    /// Days studied in the longest streak ever.
+    pub longest: u16,
+    /// First day of the ongoing streak.
+    pub start: Option<Date>,
+    /// Whether today already counts towards the ongoing streak.
+    pub today_counted: bool,
+    /// Whether a streak freeze bridged the gap before the last day studied (or before today, if not studied yet).
+    pub freeze_bridged: bool,
+    /// Whether the previous streak broke by a single missed day, which a streak freeze on yesterday would bridge.
+    pub freezable: bool
+}
+
+/// Computes the streak from the days a user studied and the days that were frozen,
+/// both in ascending order without duplicates.
+/// A streak survives up to grace_days days without studying in a row.
+/// Frozen days keep the streak alive, but do not count as studied.
+pub fn compute_streak(days: &[Date], frozen_days: &[Date], today: Date, grace_days: u16) -> Streak {
+    let max_gap = grace_days as i64 + 1;
+
+    let mut events = days
+        .iter()
+        .map(|&d| (d, true))
+        .chain(frozen_days
+            .iter()
+            .filter(|d| !days.contains(d))
+            .map(|&d| (d, false)))
+        .collect::<Vec<_>>();
+    events.sort();
+
+    let mut streak = Streak::default();
+    let mut run = 0u16;
+    let mut run_start = None;
+    let mut last: Option<Date> = None;
+    let mut last_studied = None;
+    let mut bridged = false;
+    let mut frozen_since_studied = false;
+
+    for &(day, studied) in &events {
+        let continues = last.is_some_and(|last| (day - last).whole_days() <= max_gap);
+
+        if !continues {
+            // A frozen day cannot start a streak.
+            if !studied {
+                continue
+            }
+
+            run = 0;
+            run_start = Some(day);
+            frozen_since_studied = false;
+        }
+
+        if studied {
+            run += 1;
+            streak.longest = streak.longest.max(run);
+            bridged = frozen_since_studied;
+            frozen_since_studied = false;
+            last_studied = Some(day);
+        } else {
+            frozen_since_studied = true;
+        }
+
+        last = Some(day);
+    }
+
+    if let Some(last) = last {
+        if (today - last).whole_days() <= max_gap {
+            streak.current = run;
+            streak.start = run_start;
+            streak.today_counted = last_studied == Some(today);
+            streak.freeze_bridged = if streak.today_counted { bridged } else { frozen_since_studied };
+        }
+    }
+
+    streak
+}
+
+/// Days a user studied, in ascending order without duplicates, from their sessions as (started, ended, length).
+/// A session that spans midnight counts for each day it has enough time on.
+fn study_days(sessions: impl IntoIterator<Item = (Option<OffsetDateTime>, OffsetDateTime, i64)>, tz: Tz, min_length: i64) -> Vec<Date> {
+    let mut days = sessions
+        .into_iter()
+        .flat_map(|(started, ended, length)| {
+            let started = started.unwrap_or(ended - time::Duration::seconds(length));
+            split_across_days(started, ended, tz)
+                .into_iter()
+                .filter(move |(_, part)| length as f64 * part >= min_length as f64)
+                .map(|(day, _)| day)
+        })
+        .collect::<Vec<_>>();
+    days.sort();
+    days.dedup();
+    days
+}
+
+/// Like [`compute_streak`], also telling whether a streak freeze on yesterday would revive the streak.
+fn streak_at(study_days: &[Date], frozen_days: &[Date], tz: Tz, rules: &StudyRules) -> Streak {
+    let today = local_date(OffsetDateTime::now_utc(), tz);
+    let mut streak = compute_streak(study_days, frozen_days, today, rules.streak_grace_days);
+
+    let last = study_days
+        .iter()
+        .chain(frozen_days)
+        .filter(|&&d| d < today)
+        .max();
+    streak.freezable = last
+        .is_some_and(|&last| (today - last).whole_days() == rules.streak_grace_days as i64 + 2);
+
+    streak
+}
+
+/// Streak of a user, with days in the user's timezone.
+pub async fn user_streak(ctx: &ActOnUser<'_>, rules: &StudyRules) -> Streak {
+    let uid = ctx.uid();
+    let min_length = (rules.streak_min_session_minutes * 60) as i64;
+    let tz = user_timezone(ctx).await;
+
+    let sessions = sqlx::query!(r#"
+    SELECT
+        started AS "started: OffsetDateTime",
+        ended AS "ended: OffsetDateTime",
+        length
+    FROM study_sessions
+    WHERE
+        user_id IN (SELECT id FROM users WHERE uid = $1) AND
+        length >= $2
+    "#, uid, min_length)
+        .fetch_all(ctx.0)
+        .await.unwrap()
+        .into_iter()
+        .map(|r| (r.started, r.ended, r.length));
+
+    let frozen_days = sqlx::query!(r#"
+    SELECT day AS "day: Date"
+    FROM frozen_streak_days
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ORDER BY day
+    "#, uid)
+        .fetch_all(ctx.0)
+        .await.unwrap()
+        .into_iter()
+        .map(|r| r.day)
+        .collect::<Vec<_>>();
+
+    streak_at(&study_days(sessions, tz, min_length), &frozen_days, tz, rules)
+}
+
+/// Streaks of every user that has studied, like [`user_streak`] but with a few queries for all of them.
+pub async fn all_streaks(pool: &SqlitePool, rules: &StudyRules) -> Vec<(UserId, Streak)> {
+    let min_length = (rules.streak_min_session_minutes * 60) as i64;
+
+    let mut sessions = HashMap::<i64, Vec<_>>::new();
+    for r in sqlx::query!(r#"
+    SELECT
+        uid,
+        started AS "started: OffsetDateTime",
+        ended AS "ended: OffsetDateTime",
+        length
+    FROM study_sessions
+    JOIN users ON user_id = users.id
+    WHERE length >= $1
+    "#, min_length)
+        .fetch_all(pool)
+        .await.unwrap() {
+        sessions.entry(r.uid).or_default().push((r.started, r.ended, r.length));
+    }
+
+    let mut frozen_days = HashMap::<i64, Vec<_>>::new();
+    for r in sqlx::query!(r#"
+    SELECT uid, day AS "day: Date"
+    FROM frozen_streak_days
+    JOIN users ON user_id = users.id
+    ORDER BY day
+    "#)
+        .fetch_all(pool)
+        .await.unwrap() {
+        frozen_days.entry(r.uid).or_default().push(r.day);
+    }
+
+    let timezones = sqlx::query!("
+    SELECT uid, timezone
+    FROM user_timezones
+    JOIN users ON user_id = users.id
+    ")
+        .fetch_all(pool)
+        .await.unwrap()
+        .into_iter()
+        .filter_map(|r| Some((r.uid, r.timezone.parse::<Tz>().ok()?)))
+        .collect::<HashMap<_, _>>();
+
+    sessions
+        .into_iter()
+        .map(|(uid, sessions)| {
+            let tz = timezones.get(&uid).copied().unwrap_or(Tz::UTC);
+            let frozen_days = frozen_days.remove(&uid).unwrap_or_default();
+            let streak = streak_at(&study_days(sessions, tz, min_length), &frozen_days, tz, rules);
+            (UserId::new(uid as u64), streak)
+        })
+        .collect()
+}
+
+/// Uses one of the user's streak freezes on yesterday, if the user has any.
+/// Returns whether a streak freeze was used.
+pub async fn freeze_yesterday(ctx: &ActOnUser<'_>) -> bool {
+    if !use_item(ctx, Item::StreakFreeze).await {
+        return false
+    }
+
+    let uid = ctx.uid();
+    let tz = user_timezone(ctx).await;
+    let yesterday = local_date(OffsetDateTime::now_utc(), tz).previous_day().unwrap();
+
+    sqlx::query!("
+    INSERT OR IGNORE INTO frozen_streak_days (user_id, day)
+    SELECT id, $2 FROM users WHERE uid = $1
+    ", uid, yesterday)
+        .execute(ctx.0)
+        .await.unwrap();
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Date, Month};
+
+    use super::{compute_streak, Streak};
+
+    fn day(day: u8) -> Date {
+        Date::from_calendar_date(2024, Month::November, day).unwrap()
+    }
+
+    fn days(days: &[u8]) -> Vec<Date> {
+        days.iter().map(|&d| day(d)).collect()
+    }
+
+    #[test]
+    fn no_days() {
+        assert!(compute_streak(&[], &[], day(10), 0) == Streak::default());
+    }
+
+    #[test]
+    fn consecutive_days_up_to_today() {
+        let streak = compute_streak(&days(&[8, 9, 10]), &[], day(10), 0);
+
+        assert_eq!(streak.current, 3);
+        assert_eq!(streak.longest, 3);
+        assert_eq!(streak.start, Some(day(8)));
+        assert!(streak.today_counted);
+        assert!(!streak.freeze_bridged);
+    }
+
+    #[test]
+    fn today_not_counted_yet() {
+        let streak = compute_streak(&days(&[8, 9]), &[], day(10), 0);
+
+        assert_eq!(streak.current, 2);
+        assert_eq!(streak.start, Some(day(8)));
+        assert!(!streak.today_counted);
+    }
+
+    #[test]
+    fn missed_day_breaks_streak() {
+        let streak = compute_streak(&days(&[6, 7, 8]), &[], day(10), 0);
+
+        assert_eq!(streak.current, 0);
+        assert_eq!(streak.longest, 3);
+        assert_eq!(streak.start, None);
+    }
+
+    #[test]
+    fn gap_longer_than_grace_starts_new_streak() {
+        let streak = compute_streak(&days(&[1, 2, 3, 7, 8]), &[], day(8), 1);
+
+        assert_eq!(streak.current, 2);
+        assert_eq!(streak.longest, 3);
+        assert_eq!(streak.start, Some(day(7)));
+    }
+
+    #[test]
+    fn grace_days_keep_streak() {
+        let streak = compute_streak(&days(&[5, 7, 8]), &[], day(10), 1);
+
+        assert_eq!(streak.current, 3);
+        assert_eq!(streak.start, Some(day(5)));
+        assert!(!streak.today_counted);
+
+        let streak = compute_streak(&days(&[5, 7, 8]), &[], day(11), 1);
+
+        assert_eq!(streak.current, 0);
+    }
+
+    #[test]
+    fn frozen_day_bridges_gap_without_counting() {
+        let streak = compute_streak(&days(&[7, 8, 10]), &days(&[9]), day(10), 0);
+
+        assert_eq!(streak.current, 3);
+        assert_eq!(streak.start, Some(day(7)));
+        assert!(streak.today_counted);
+        assert!(streak.freeze_bridged);
+    }
+
+    #[test]
+    fn frozen_day_before_today_is_bridged() {
+        let streak = compute_streak(&days(&[7, 8]), &days(&[9]), day(10), 0);
+
+        assert_eq!(streak.current, 2);
+        assert!(!streak.today_counted);
+        assert!(streak.freeze_bridged);
+    }
+
+    #[test]
+    fn frozen_day_cannot_start_streak() {
+        let streak = compute_streak(&[], &days(&[9]), day(10), 0);
+
+        assert!(streak == Streak::default());
+
+        let streak = compute_streak(&days(&[10]), &days(&[5]), day(10), 0);
+
+        assert_eq!(streak.current, 1);
+        assert_eq!(streak.start, Some(day(10)));
+        assert!(!streak.freeze_bridged);
+    }
+
+    #[test]
+    fn frozen_day_on_studied_day_is_ignored() {
+        let streak = compute_streak(&days(&[8, 9, 10]), &days(&[9]), day(10), 0);
+
+        assert_eq!(streak.current, 3);
+        assert!(!streak.freeze_bridged);
+    }
+}
@@ -1,14 +1,14 @@
-use std::time::Duration;
+use std::{collections::{HashMap, HashSet}, sync::Arc, time::Duration};
 
 use humantime::format_duration;
-use poise::serenity_prelude::{futures::{future::join_all, lock::Mutex}, ButtonStyle, CacheHttp, ChannelId, Context, CreateButton, CreateMessage, FutureExt, Mentionable, MessageBuilder, User, UserId, VoiceState};
+use log::{error, info};
+use poise::serenity_prelude::{futures::{future::join_all, lock::Mutex}, ActivityData, ButtonStyle, CacheHttp, CreateActionRow, ChannelId, Context, CreateButton, CreateMessage, FutureExt, Guild, GuildId, Mentionable, MessageBuilder, OnlineStatus, User, UserId, VoiceState};
 use rand::Rng;
-use sqlx::types::time::OffsetDateTime;
-use tokio::time::Instant;
+use sqlx::{types::time::OffsetDateTime, SqlitePool};
 
-use crate::{leaderboard::{real_leaderboard_start_datetime, user_place}, prelude::{try_dm_or_in_guild, ActOnUser}, rewards::{user_claim_reward, Reward}, Channels, Data, Error};
+use crate::{boosters::{apply_boosters, user_boosters}, goals::{complete_goals, user_goal_progress, GoalProgress}, leaderboard::{fetch_leaderboard, user_leaderboard_optout, user_place, LeaderboardMetric, LeaderboardPeriod}, notifications::{notify_overtaken, NOTIFIED_PLACES}, prelude::{elapsed_between, try_dm_or_in_guild, ActOnUser}, rewards::{user_claim_reward, Reward}, streak::{freeze_yesterday, user_streak, Streak}, subjects::{subject_menu, tag_session, user_subject_preset, user_subjects}, top_roles::sync_top_roles, Channels, Data, Error, StudyEarnings};
 
-fn is_study_vc(channels_config: &Channels, channel_id: ChannelId) -> bool {
+pub fn is_study_vc(channels_config: &Channels, channel_id: ChannelId) -> bool {
     !channels_config.slacking_voice_channels.contains(&u64::from(channel_id))
 }
 
@@ -17,14 +17,26 @@ fn is_voice_state_studying(channels_config: &Channels, state: &VoiceState) -> bo
         .unwrap_or(false)
 }
 
+pub type StudyStates = Arc<Mutex<HashMap<UserId, StudyState>>>;
+
 pub struct StudyState {
-    pub start: Instant,
+    pub start: OffsetDateTime,
 
-    pub video_start: Mutex<Option<Instant>>,
+    pub video_start: Mutex<Option<OffsetDateTime>>,
     pub video_sum: Mutex<Duration>,
 
-    pub break_start: Mutex<Option<Instant>>,
-    pub break_sum: Mutex<Duration>
+    pub break_start: Mutex<Option<OffsetDateTime>>,
+    pub break_sum: Mutex<Duration>,
+    /// Breaks that have ended, as (start, end).
+    pub breaks: Mutex<Vec<(OffsetDateTime, OffsetDateTime)>>,
+    /// Whether the ongoing break was started by a Pomodoro cycle.
+    /// Cycles do not survive a restart, so these breaks are ended when the state is restored.
+    pub pomodoro_break: Mutex<bool>,
+
+    /// When the user left voice, if they did and may still rejoin within the reconnect grace window.
+    pub left_at: Mutex<Option<OffsetDateTime>>,
+    /// Times the user was away from voice before rejoining, as (start, end).
+    pub absences: Mutex<Vec<(OffsetDateTime, OffsetDateTime)>>
 }
 
 /// Only for visual representation.
@@ -44,31 +56,407 @@ pub struct StudyResult<'a> {
     leaderboard_place: Option<(u16, Option<u16>)>,
 
     coins: u64,
+    /// Coins before boosters were applied.
+    base_coins: u64,
+    /// (multiplier, time active during the session)
+    boosters: Vec<(u16, Duration)>,
 
     /// (after, before)
-    streak: (u16, u16)
+    streak: (Streak, Streak),
+
+    subject: Option<String>,
+    /// Earlier subjects of the user, to choose from.
+    subjects: Vec<String>,
+
+    goals: Vec<GoalProgress>
 }
 
 impl StudyState {
+    pub fn new(start: OffsetDateTime) -> Self {
+        StudyState {
+            start,
+
+            video_start: None.into(),
+            video_sum: Duration::ZERO.into(),
+
+            break_start: None.into(),
+            break_sum: Duration::ZERO.into(),
+            breaks: Vec::new().into(),
+            pomodoro_break: false.into(),
+
+            left_at: None.into(),
+            absences: Vec::new().into()
+        }
+    }
+
     /// Moves the current progress from video_start to video_prev_total.
     /// Used when video ends, to summarize.
-    async fn sum_video_progress(&self) {
+    async fn sum_video_progress(&self, until: OffsetDateTime) {
         let mut start = self.video_start.lock().await;
-        if let Some(inst) = *start {
+        if let Some(t) = *start {
             let mut sum = self.video_sum.lock().await;
-            *sum += inst.elapsed();
+            *sum += elapsed_between(t, until);
             *start = None;
         }
     }
 
-    async fn sum_break_progress(&self) {
+    async fn sum_break_progress(&self, until: OffsetDateTime) {
         let mut start = self.break_start.lock().await;
-        if let Some(inst) = *start {
+        if let Some(t) = *start {
             let mut sum = self.break_sum.lock().await;
-            *sum += inst.elapsed();
+            *sum += elapsed_between(t, until);
+            self.breaks.lock().await.push((t, until));
+            *self.pomodoro_break.lock().await = false;
             *start = None;
         }
     }
+
+    /// Time studied until a point in time, without the time away from voice.
+    pub async fn length_at(&self, at: OffsetDateTime) -> Duration {
+        let away = self.absences.lock().await
+            .iter()
+            .map(|&(start, end)| elapsed_between(start, end))
+            .sum::<Duration>();
+        elapsed_between(self.start, at).saturating_sub(away)
+    }
+
+    /// Video time until a point in time, including the ongoing stream.
+    pub async fn video_length_at(&self, at: OffsetDateTime) -> Duration {
+        let running = self.video_start.lock().await
+            .map(|t| elapsed_between(t, at))
+            .unwrap_or_default();
+        *self.video_sum.lock().await + running
+    }
+
+    /// Break time until a point in time, including the ongoing break.
+    pub async fn break_length_at(&self, at: OffsetDateTime) -> Duration {
+        let running = self.break_start.lock().await
+            .map(|t| elapsed_between(t, at))
+            .unwrap_or_default();
+        *self.break_sum.lock().await + running
+    }
+
+    /// Breaks until a point in time, including the ongoing break.
+    pub async fn breaks_at(&self, at: OffsetDateTime) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+        let mut breaks = self.breaks.lock().await.clone();
+        if let Some(t) = *self.break_start.lock().await {
+            breaks.push((t, at));
+        }
+        breaks
+    }
+
+    /// Times not studied until a point in time, as (start, end): breaks, including the ongoing one,
+    /// and time away from voice. Boosters are not active during them.
+    pub async fn not_studying_at(&self, at: OffsetDateTime) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+        let mut not_studying = self.breaks_at(at).await;
+        not_studying.extend(self.absences.lock().await.iter().copied());
+        not_studying
+    }
+
+    pub async fn on_break(&self) -> bool {
+        self.break_start.lock().await.is_some()
+    }
+
+    /// Whether the user left voice and may still rejoin within the reconnect grace window.
+    pub async fn is_away(&self) -> bool {
+        self.left_at.lock().await.is_some()
+    }
+
+    /// Continues the session of a user that rejoined within the reconnect grace window.
+    /// Returns whether the user was away.
+    async fn resume(&self, at: OffsetDateTime) -> bool {
+        let Some(left_at) = self.left_at.lock().await.take() else { return false };
+
+        // The time away is not part of a break, since it is not part of the session at all.
+        if self.on_break().await {
+            let pomodoro_break = *self.pomodoro_break.lock().await;
+            self.set_on_break(false, left_at).await;
+            self.set_on_break(true, at).await;
+            *self.pomodoro_break.lock().await = pomodoro_break;
+        }
+
+        self.absences.lock().await.push((left_at, at));
+        true
+    }
+
+    /// Starts or ends a break.
+    /// Returns whether the break state changed.
+    async fn set_on_break(&self, on_break: bool, at: OffsetDateTime) -> bool {
+        let mut start = self.break_start.lock().await;
+        match (*start, on_break) {
+            (None, true) => {
+                *start = Some(at);
+                true
+            }
+            (Some(_), false) => {
+                drop(start);
+                self.sum_break_progress(at).await;
+                true
+            }
+            _ => false
+        }
+    }
+
+    /// Ends the break if on one, otherwise starts one.
+    /// Returns whether now on a break.
+    async fn toggle_break(&self, at: OffsetDateTime) -> bool {
+        let mut start = self.break_start.lock().await;
+        match *start {
+            None => {
+                *start = Some(at);
+                true
+            }
+            Some(t) => {
+                *self.break_sum.lock().await += elapsed_between(t, at);
+                self.breaks.lock().await.push((t, at));
+                *self.pomodoro_break.lock().await = false;
+                *start = None;
+                false
+            }
+        }
+    }
+
+    /// Saves the state in the database, so that it survives a restart.
+    /// Should be called whenever the state changes.
+    pub async fn persist(&self, ctx: &ActOnUser<'_>) {
+        let uid = ctx.uid();
+        let started = self.start.unix_timestamp();
+        let video_start = self.video_start.lock().await.map(|t| t.unix_timestamp());
+        let video_sum = self.video_sum.lock().await.as_secs() as i64;
+        let break_start = self.break_start.lock().await.map(|t| t.unix_timestamp());
+        let break_sum = self.break_sum.lock().await.as_secs() as i64;
+        let pomodoro_break = *self.pomodoro_break.lock().await;
+        let left_at = self.left_at.lock().await.map(|t| t.unix_timestamp());
+
+        sqlx::query!("
+        INSERT OR REPLACE INTO active_study_sessions
+            (user_id, started, video_start, video_sum, break_start, break_sum, pomodoro_break, left_at, last_seen)
+        SELECT id, $2, $3, $4, $5, $6, $7, $8, UNIXEPOCH() FROM users WHERE uid = $1
+        ", uid, started, video_start, video_sum, break_start, break_sum, pomodoro_break, left_at)
+            .execute(ctx.0)
+            .await.unwrap();
+
+        sqlx::query!("
+        DELETE FROM active_study_breaks
+        WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+        ", uid)
+            .execute(ctx.0)
+            .await.unwrap();
+
+        for (start, end) in self.breaks.lock().await.iter() {
+            let (start, end) = (start.unix_timestamp(), end.unix_timestamp());
+
+            sqlx::query!("
+            INSERT INTO active_study_breaks (user_id, started, ended)
+            SELECT id, $2, $3 FROM users WHERE uid = $1
+            ", uid, start, end)
+                .execute(ctx.0)
+                .await.unwrap();
+        }
+
+        sqlx::query!("
+        DELETE FROM active_study_absences
+        WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+        ", uid)
+            .execute(ctx.0)
+            .await.unwrap();
+
+        for (start, end) in self.absences.lock().await.iter() {
+            let (start, end) = (start.unix_timestamp(), end.unix_timestamp());
+
+            sqlx::query!("
+            INSERT INTO active_study_absences (user_id, started, ended)
+            SELECT id, $2, $3 FROM users WHERE uid = $1
+            ", uid, start, end)
+                .execute(ctx.0)
+                .await.unwrap();
+        }
+    }
+}
+
+/// Loads the study states saved by [`StudyState::persist`].
+pub async fn restore_study_states(pool: &SqlitePool) -> HashMap<UserId, StudyState> {
+    let timestamp = |t: i64| OffsetDateTime::from_unix_timestamp(t).unwrap();
+
+    let mut breaks = HashMap::<i64, Vec<_>>::new();
+    for r in sqlx::query!("
+    SELECT uid, started, ended
+    FROM active_study_breaks
+    JOIN users ON user_id = users.id
+    ORDER BY started
+    ")
+        .fetch_all(pool)
+        .await.unwrap() {
+        breaks.entry(r.uid).or_default().push((timestamp(r.started), timestamp(r.ended)));
+    }
+
+    let mut absences = HashMap::<i64, Vec<_>>::new();
+    for r in sqlx::query!("
+    SELECT uid, started, ended
+    FROM active_study_absences
+    JOIN users ON user_id = users.id
+    ORDER BY started
+    ")
+        .fetch_all(pool)
+        .await.unwrap() {
+        absences.entry(r.uid).or_default().push((timestamp(r.started), timestamp(r.ended)));
+    }
+
+    let mut study_states = HashMap::new();
+
+    for r in sqlx::query!(r#"
+    SELECT uid, started, video_start, video_sum, break_start, break_sum, pomodoro_break AS "pomodoro_break: bool", left_at, last_seen
+    FROM active_study_sessions
+    JOIN users ON user_id = users.id
+    "#)
+        .fetch_all(pool)
+        .await.unwrap() {
+        let state = StudyState {
+            start: timestamp(r.started),
+
+            video_start: r.video_start.map(timestamp).into(),
+            video_sum: Duration::from_secs(r.video_sum as u64).into(),
+
+            break_start: r.break_start.map(timestamp).into(),
+            break_sum: Duration::from_secs(r.break_sum as u64).into(),
+            breaks: breaks.remove(&r.uid).unwrap_or_default().into(),
+            pomodoro_break: r.pomodoro_break.into(),
+
+            left_at: r.left_at.map(timestamp).into(),
+            absences: absences.remove(&r.uid).unwrap_or_default().into()
+        };
+
+        // Nothing is left to end the break of the Pomodoro cycle, so it ends when the bot went down.
+        let user_id = UserId::new(r.uid as u64);
+
+        if let (true, Some(break_start)) = (r.pomodoro_break, r.break_start) {
+            state.set_on_break(false, timestamp(r.last_seen)).await;
+
+            // Saved without persisting, which would move last_seen that stale sessions end at.
+            let break_sum = state.break_sum.lock().await.as_secs() as i64;
+
+            sqlx::query!("
+            UPDATE active_study_sessions
+            SET break_start = NULL, break_sum = $2, pomodoro_break = FALSE
+            WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+            ", r.uid, break_sum)
+                .execute(pool)
+                .await.unwrap();
+
+            sqlx::query!("
+            INSERT INTO active_study_breaks (user_id, started, ended)
+            SELECT id, $2, $3 FROM users WHERE uid = $1
+            ", r.uid, break_start, r.last_seen)
+                .execute(pool)
+                .await.unwrap();
+        }
+
+        study_states.insert(user_id, state);
+    }
+
+    study_states
+}
+
+/// Marks every saved study state as active right now.
+/// If the bot goes down, this is the time the sessions will end at,
+/// unless their owners are still studying when it comes back.
+pub async fn touch_study_states(pool: &SqlitePool) {
+    sqlx::query!("UPDATE active_study_sessions SET last_seen = UNIXEPOCH()")
+        .execute(pool)
+        .await.unwrap();
+}
+
+/// Shows how many people are studying in the bot's presence.
+pub async fn update_study_presence(ctx: &Context, pool: &SqlitePool) {
+    let studying = sqlx::query!("SELECT COUNT(*) AS count FROM active_study_sessions WHERE left_at IS NULL")
+        .fetch_one(pool)
+        .await.unwrap()
+        .count;
+
+    ctx.set_presence(
+        Some(ActivityData::watching(match studying {
+            0 => "you".to_string(),
+            1 => "1 person study".to_string(),
+            n => format!("{} people study", n)
+        })),
+        OnlineStatus::Idle);
+}
+
+/// Brings the study states in line with who is actually in the guild's voice channels,
+/// since voice state updates are missed while the bot is offline.
+/// Sessions that ended while the bot was offline are closed by [`close_stale_sessions`].
+pub async fn reconcile_voice_states(ctx: &Context, data: &Data, guild: &Guild) -> Result<(), Error> {
+    for voice_state in guild.voice_states.values() {
+        if !is_voice_state_studying(&data.config.channels, voice_state) {
+            continue
+        }
+
+        let studying = match data.study_states.lock().await.get(&voice_state.user_id) {
+            Some(state) => !state.is_away().await,
+            None => false
+        };
+
+        if !studying {
+            info!("Found {} already studying", voice_state.user_id);
+            begin_studying(ctx, data, voice_state.user_id).await;
+        }
+
+        video_state_update(ctx, data, voice_state).await;
+
+        if data.config.study_rules.deafen_is_break {
+            deafen_state_update(data, None, voice_state).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Finishes the sessions of users that are no longer studying in any of the guilds,
+/// e.g. because they left voice while the bot was offline.
+/// These sessions end at the time they were last known to be active.
+/// Only run once every guild is cached, since users may be studying in any of them.
+pub async fn close_stale_sessions(ctx: &Context, data: &Data, guild_ids: &[GuildId]) -> Result<(), Error> {
+    let studying = guild_ids
+        .iter()
+        .filter_map(|&guild_id| ctx.cache.guild(guild_id).map(|guild| guild.voice_states
+            .values()
+            .filter(|vs| is_voice_state_studying(&data.config.channels, vs))
+            .map(|vs| vs.user_id)
+            .collect::<Vec<_>>()))
+        .flatten()
+        .collect::<HashSet<_>>();
+
+    let mut study_states = data.study_states.lock().await;
+
+    let stale_users = study_states
+        .keys()
+        .filter(|user_id| !studying.contains(user_id))
+        .copied()
+        .collect::<Vec<_>>();
+
+    for user_id in stale_users {
+        let Some(state) = study_states.remove(&user_id) else { continue };
+
+        let uid = i64::from(user_id);
+        let last_seen = sqlx::query!("
+        SELECT last_seen FROM active_study_sessions
+        WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+        ", uid)
+            .fetch_optional(&data.db_pool)
+            .await?
+            .map(|r| OffsetDateTime::from_unix_timestamp(r.last_seen))
+            .transpose()?
+            .unwrap_or_else(OffsetDateTime::now_utc);
+
+        // A user who left right before, ends at the time they left.
+        let end = state.left_at.lock().await.unwrap_or(last_seen);
+
+        info!("Closing stale study session of {}, ending {}", user_id, end);
+
+        finish_session(ctx, data, user_id, state, end, true).await;
+    }
+
+    Ok(())
 }
 
 pub async fn voice_state_update(ctx: &Context, data: &Data, old: Option<&VoiceState>, new: &VoiceState) -> Result<(), Error> {
@@ -77,60 +465,138 @@ pub async fn voice_state_update(ctx: &Context, data: &Data, old: Option<&VoiceSt
         .unwrap_or(false);
     let study_now = is_voice_state_studying(&data.config.channels, new);
 
-    match (study_before, study_now) {
-        (false, true) => begin_studying(ctx, data, new.user_id).await,
-        (true, false) => end_studying(ctx, data, new.user_id).await,
-        _ => ()
+    if !study_before && study_now {
+        begin_studying(ctx, data, new.user_id).await;
     }
 
     video_state_update(ctx, data, new).await;
 
+    if data.config.study_rules.deafen_is_break {
+        deafen_state_update(data, old.filter(|_| study_before), new).await;
+    }
+
+    if study_before && !study_now {
+        end_studying(ctx, data, new.user_id).await;
+    }
+
     Ok(())
 }
 
 async fn begin_studying(ctx: &Context, data: &Data, user_id: UserId) {
     let mut study_states = data.study_states.lock().await;
 
-    study_states.insert(user_id, StudyState {
-        start: Instant::now(),
+    if let Some(state) = study_states.get(&user_id) {
+        if state.resume(OffsetDateTime::now_utc()).await {
+            info!("{} rejoined within the reconnect grace window", user_id);
+            state.persist(&ActOnUser(&data.db_pool, user_id)).await;
+        }
+        return
+    }
 
-        video_start: None.into(),
-        video_sum: Duration::ZERO.into(),
+    let state = StudyState::new(OffsetDateTime::now_utc());
+    state.persist(&ActOnUser(&data.db_pool, user_id)).await;
 
-        break_start: None.into(),
-        break_sum: Duration::ZERO.into()
-    });
+    study_states.insert(user_id, state);
 }
 
+/// Finishes the session of a user that left, unless they rejoin within the reconnect grace window.
 async fn end_studying(ctx: &Context, data: &Data, user_id: UserId) {
+    let left_at = OffsetDateTime::now_utc();
+    let grace = Duration::from_secs(data.config.study_rules.reconnect_grace_seconds);
+
     let mut study_states = data.study_states.lock().await;
+    let Some(state) = study_states.get(&user_id) else { return };
 
-    let Some(state) = study_states.remove(&user_id) else { return };
-    finish_session(ctx, data, user_id, state, true).await;
+    if grace.is_zero() {
+        let state = study_states.remove(&user_id).unwrap();
+        finish_session(ctx, data, user_id, state, left_at, true).await;
+        return
+    }
+
+    *state.left_at.lock().await = Some(left_at);
+    state.persist(&ActOnUser(&data.db_pool, user_id)).await;
+    drop(study_states);
+
+    let ctx = ctx.clone();
+    let data = data.clone();
+
+    tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+
+        let mut study_states = data.study_states.lock().await;
+
+        // Rejoined in time, or the session was already finished elsewhere.
+        let Some(state) = study_states.get(&user_id) else { return };
+        if *state.left_at.lock().await != Some(left_at) {
+            return
+        }
+
+        let state = study_states.remove(&user_id).unwrap();
+        finish_session(&ctx, &data, user_id, state, left_at, true).await;
+    });
+}
+
+/// Coins earned for a session before boosters.
+pub fn base_coins(earnings: &StudyEarnings, length: Duration, break_length: Duration) -> u64 {
+    (length.saturating_sub(break_length).as_secs() / 60) * earnings.coins_per_minute
 }
 
-pub async fn finish_session(ctx: &Context, data: &Data, user_id: UserId, state: StudyState, alert: bool) {
-    state.sum_video_progress().await;
-    state.sum_break_progress().await;
+pub async fn finish_session(ctx: &Context, data: &Data, user_id: UserId, state: StudyState, end: OffsetDateTime, alert: bool) {
+    state.sum_video_progress(end).await;
+    state.sum_break_progress(end).await;
 
-    let length = state.start.elapsed();
+    let length = state.length_at(end).await;
+    let not_studying = state.not_studying_at(end).await;
     let video_length = state.video_sum.into_inner();
+    let break_length = state.break_sum.into_inner().min(length);
 
-    let coins = (length.as_secs() / 60)
-        * data.config.study_earnings.coins_per_minute;
+    let base_coins = base_coins(&data.config.study_earnings, length, break_length);
     let uid = i64::from(user_id);
 
     let act_on_user_ctx =
         &ActOnUser(&data.db_pool, user_id);
 
-    let lb_start = real_leaderboard_start_datetime();
+    let boosters = user_boosters(act_on_user_ctx, state.start, end).await;
+    let coins = apply_boosters(
+        base_coins,
+        &boosters,
+        data.config.study_earnings.booster_stacking,
+        state.start,
+        end,
+        &not_studying);
+
+    sqlx::query!("
+    DELETE FROM active_study_sessions
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ", uid)
+        .execute(&data.db_pool)
+        .await.unwrap();
+
+    sqlx::query!("
+    DELETE FROM active_study_breaks
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ", uid)
+        .execute(&data.db_pool)
+        .await.unwrap();
+
+    sqlx::query!("
+    DELETE FROM active_study_absences
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ", uid)
+        .execute(&data.db_pool)
+        .await.unwrap();
+
+    let (lb_start, lb_end) = LeaderboardPeriod::Month.bounds();
+    let lb_optout = user_leaderboard_optout(act_on_user_ctx).await;
 
     let lb_place_before =
-        user_place(act_on_user_ctx, lb_start).await;
+        user_place(act_on_user_ctx, &data.config.study_rules, LeaderboardMetric::StudyTime, lb_start, lb_end).await;
+    let lb_top_before =
+        fetch_leaderboard(&data.db_pool, &data.config.study_rules, LeaderboardMetric::StudyTime, lb_start, lb_end, Some(NOTIFIED_PLACES), 0).await;
 
     let user = ctx.http().get_user(user_id).await.unwrap();
 
-    let streak_before = user_streak(act_on_user_ctx).await;
+    let streak_before = user_streak(act_on_user_ctx, &data.config.study_rules).await;
 
     let session_id = {
         let coins = coins as i64;
@@ -146,30 +612,53 @@ pub async fn finish_session(ctx: &Context, data: &Data, user_id: UserId, state:
 
         let length = length.as_secs() as i64;
         let video_length = video_length.as_secs() as i64;
+        let break_length = break_length.as_secs() as i64;
 
-        let ended = OffsetDateTime::now_utc();
+        let started = state.start;
+        let ended = end;
 
         sqlx::query!("
-        INSERT INTO study_sessions (user_id, coin_reward_id, length, video_length, ended)
-        SELECT users.id, $2, $3, $4, $5 FROM users WHERE uid = $1
-        ", uid, coin_reward_id, length, video_length, ended)
+        INSERT INTO study_sessions (user_id, coin_reward_id, length, video_length, break_length, started, ended)
+        SELECT users.id, $2, $3, $4, $5, $6, $7 FROM users WHERE uid = $1
+        ", uid, coin_reward_id, length, video_length, break_length, started, ended)
             .execute(&data.db_pool)
             .await
             .unwrap()
             .last_insert_rowid()
     };
 
+    let subject = user_subject_preset(act_on_user_ctx).await;
+    if let Some(subject) = &subject {
+        tag_session(&data.db_pool, session_id, subject).await;
+    }
+
     let lb_place_after =
-        user_place(act_on_user_ctx, lb_start).await;
+        user_place(act_on_user_ctx, &data.config.study_rules, LeaderboardMetric::StudyTime, lb_start, lb_end).await;
+
+    if lb_place_after != lb_place_before {
+        if let Err(e) = sync_top_roles(ctx, &data.db_pool, &data.config).await {
+            error!("Top role sync failed: {:?}", e);
+        }
+    }
 
-    let streak_after = user_streak(act_on_user_ctx).await;
+    let mut streak_after = user_streak(act_on_user_ctx, &data.config.study_rules).await;
+
+    // Studying today revives a streak that broke yesterday, if the user has a streak freeze for it.
+    if streak_after.today_counted && streak_after.freezable && freeze_yesterday(act_on_user_ctx).await {
+        streak_after = user_streak(act_on_user_ctx, &data.config.study_rules).await;
+    }
 
     let mut rewards = Vec::new();
 
-    if streak_before != streak_after {
+    if !streak_before.today_counted && streak_after.today_counted {
         rewards.push("Daily reward");
     }
 
+    let goals = user_goal_progress(act_on_user_ctx).await;
+    for period in complete_goals(act_on_user_ctx, &goals).await {
+        rewards.push(period.reward_reason());
+    }
+
     let default_reward_time = random_video_reward_time().as_secs() as i64;
 
     sqlx::query!("
@@ -236,8 +725,6 @@ pub async fn finish_session(ctx: &Context, data: &Data, user_id: UserId, state:
     }).collect::<Vec<_>>()).await;
 
     if alert {
-        let now = OffsetDateTime::now_utc();
-
         let mut messages = Vec::new();
 
         let next_video_reward = if !video_length.is_zero() {
@@ -255,16 +742,26 @@ pub async fn finish_session(ctx: &Context, data: &Data, user_id: UserId, state:
             user: &user,
             session_id,
 
-            start: now - state.start.elapsed(),
-            end: now,
+            start: state.start,
+            end,
             length,
             video_length,
             next_video_reward,
-            breaks: state.break_sum.into_inner(),
+            breaks: break_length,
 
-            leaderboard_place: lb_place_after.map(|after| (after, lb_place_before)),
+            leaderboard_place: lb_place_after
+                .filter(|_| !lb_optout)
+                .map(|after| (after, lb_place_before)),
             coins,
-            streak: (streak_after, streak_before)
+            base_coins,
+            boosters: boosters
+                .iter()
+                .map(|b| (b.multiplier, b.active_during(state.start, end, &not_studying)))
+                .collect(),
+            streak: (streak_after, streak_before),
+            subject,
+            subjects: user_subjects(act_on_user_ctx).await,
+            goals
         }).await);
 
         messages.extend(claimed_rewards.iter().map(|r| {
@@ -303,6 +800,8 @@ pub async fn finish_session(ctx: &Context, data: &Data, user_id: UserId, state:
                 }
             }
         }
+
+        notify_overtaken(ctx, data, &user, &lb_top_before, lb_start, lb_end).await;
     }
 }
 
@@ -310,43 +809,25 @@ fn random_video_reward_time() -> Duration {
     Duration::from_secs(rand::thread_rng().gen_range(1..12) * 30 * 60)
 }
 
-pub async fn user_streak(ctx: &ActOnUser<'_>) -> u16 {
-    let uid = ctx.uid();
-
-    let study_days = sqlx::query!("
-    SELECT DISTINCT JULIANDAY(DATE(ended, 'unixepoch')) AS date
-    FROM study_sessions
-    WHERE
-        user_id IN (SELECT id FROM users WHERE uid = $1) AND
-        length > 10 * 60
-    ORDER BY ended DESC
-    ", uid)
-        .fetch_all(ctx.0)
-        .await.unwrap();
-
-    study_days
-        .iter()
-        .flat_map(|r| r.date)
-        .map(|r| r as i64)
-        .fold((0u16, -1i64), |acc, d| {
-            if d == acc.1 - 1 || acc.1 == -1 {
-                (acc.0 + 1, d)
-            } else {
-                (acc.0, 0)
-            }
-        }).0
-}
-
 async fn result_message(result: StudyResult<'_>) -> CreateMessage {
     let content = {
         let mut b = MessageBuilder::new();
 
-        if result.streak.0 != result.streak.1 && result.streak.0 > 1 {
+        let (streak, previous_streak) = result.streak;
+        if streak.current != previous_streak.current && streak.current > 1 {
             b.push(":wing: ");
-            b.push_bold(result.streak.1.to_string());
+            b.push_bold(previous_streak.current.to_string());
             b.push(" → ");
-            b.push_bold(result.streak.0.to_string());
-            b.push_line(" day streak!");
+            b.push_bold(streak.current.to_string());
+            if streak.current > previous_streak.longest {
+                b.push_line(" day streak! New record!");
+            } else {
+                b.push_line(format!(" day streak! (longest: **{}**)", streak.longest));
+            }
+        }
+
+        if streak.today_counted && !previous_streak.today_counted && streak.freeze_bridged {
+            b.push_line("-# :ice_cube: A streak freeze kept your streak alive.");
         }
 
         b.push(":stopwatch: ");
@@ -355,6 +836,27 @@ async fn result_message(result: StudyResult<'_>) -> CreateMessage {
             result.start.unix_timestamp(),
             result.end.unix_timestamp()));
 
+        for goal in &result.goals {
+            b.push(":dart: ");
+            b.push_bold(format_duration(Duration::from_secs(goal.studied.as_secs() / 60 * 60)).to_string());
+            b.push(format!(" / {} {}",
+                format_duration(goal.goal),
+                goal.period.current()));
+            b.push_line(if goal.is_met() { " :white_check_mark:" } else { "" });
+        }
+
+        if let Some(subject) = &result.subject {
+            b.push(":label: ");
+            b.push_bold_line_safe(subject);
+        }
+
+        if !result.breaks.is_zero() {
+            b.push(":coffee: ");
+            b.push_bold(format_duration(Duration::from_secs(result.breaks.as_secs())).to_string());
+            b.push_line(format!(" of breaks (**{}** focused)",
+                format_duration(Duration::from_secs((result.length - result.breaks).as_secs()))));
+        }
+
         if !result.video_length.is_zero() {
             let ratio = result.video_length.as_secs() as f32 / result.length.as_secs() as f32;
             b.push(":video_camera: ");
@@ -371,7 +873,18 @@ async fn result_message(result: StudyResult<'_>) -> CreateMessage {
 
         b.push(":purse: ");
         b.push_bold(format!("+{}", result.coins));
-        b.push_line(" coins");
+        if result.boosters.is_empty() {
+            b.push_line(" coins");
+        } else {
+            b.push_line(format!(" coins ({} base)", result.base_coins));
+
+            for (multiplier, active) in &result.boosters {
+                b.push("-# :zap: ");
+                b.push_bold(format!("{}x", *multiplier as f64 / 100.0));
+                b.push_line(format!(" booster active for {}",
+                    format_duration(Duration::from_secs(active.as_secs()))));
+            }
+        }
 
         match result.leaderboard_place {
             Some((current_place, None)) => {
@@ -398,11 +911,24 @@ async fn result_message(result: StudyResult<'_>) -> CreateMessage {
 
     CreateMessage::new()
         .content(content)
-        .button(
-            CreateButton::new(format!("deduct_session_{}", result.session_id))
+        .components(vec![
+            CreateActionRow::SelectMenu(
+                subject_menu(result.session_id, &result.subjects, result.subject.as_deref())),
+            result_buttons(result.session_id, false)
+        ])
+}
+
+/// Buttons under the results of a session.
+pub fn result_buttons(session_id: i64, has_note: bool) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("add_note_{}", session_id))
+            .label(if has_note { "Edit note" } else { "Add note" })
+            .emoji('📝')
+            .style(ButtonStyle::Secondary),
+        CreateButton::new(format!("deduct_session_{}", session_id))
             .label("Deduction Penalty")
-            .style(poise::serenity_prelude::ButtonStyle::Danger)
-        )
+            .style(ButtonStyle::Danger)
+    ])
 }
 
 async fn video_state_update(ctx: &Context, data: &Data, voice_state: &VoiceState) {
@@ -419,13 +945,91 @@ async fn video_state_update(ctx: &Context, data: &Data, voice_state: &VoiceState
 
     match (*stream_start, now_streaming) {
         (None, true) => {
-            *stream_start = Some(Instant::now());
+            *stream_start = Some(OffsetDateTime::now_utc());
+            drop(stream_start);
         }
         (Some(_), false) => {
             drop(stream_start);
-            state.sum_video_progress().await;
+            state.sum_video_progress(OffsetDateTime::now_utc()).await;
         }
-        _ => ()
+        _ => return
+    }
+
+    state.persist(&ActOnUser(&data.db_pool, voice_state.user_id)).await;
+}
+
+/// Self-deafening starts a break, and undeafening ends it.
+async fn deafen_state_update(data: &Data, old: Option<&VoiceState>, new: &VoiceState) {
+    let was_deafened = old.map(|vs| vs.self_deaf).unwrap_or(false);
+
+    if was_deafened != new.self_deaf {
+        set_user_on_break(data, new.user_id, new.self_deaf).await;
+    }
+}
+
+/// Starts or ends a break for a studying user.
+/// Returns None if the user is not studying, otherwise whether the break state changed.
+pub async fn set_user_on_break(data: &Data, user_id: UserId, on_break: bool) -> Option<bool> {
+    set_on_break_in(&data.study_states, &data.db_pool, user_id, on_break).await
+}
+
+/// Like [`set_user_on_break`], for when the whole [`Data`] is not at hand.
+pub async fn set_on_break_in(study_states: &StudyStates, pool: &SqlitePool, user_id: UserId, on_break: bool) -> Option<bool> {
+    let study_states = study_states.lock().await;
+
+    let state = study_states.get(&user_id)?;
+
+    let changed = state.set_on_break(on_break, OffsetDateTime::now_utc()).await;
+    if changed {
+        state.persist(&ActOnUser(pool, user_id)).await;
+    }
+
+    Some(changed)
+}
+
+/// Like [`set_on_break_in`], for the breaks of a Pomodoro cycle.
+pub async fn set_on_pomodoro_break(study_states: &StudyStates, pool: &SqlitePool, user_id: UserId, on_break: bool) -> Option<bool> {
+    let study_states = study_states.lock().await;
+
+    let state = study_states.get(&user_id)?;
+
+    let changed = state.set_on_break(on_break, OffsetDateTime::now_utc()).await;
+    if changed {
+        *state.pomodoro_break.lock().await = on_break;
+        state.persist(&ActOnUser(pool, user_id)).await;
+    }
+
+    Some(changed)
+}
+
+/// Starts a break if the user is studying, or ends it if they are on one.
+/// Returns None if the user is not studying, otherwise whether they are now on a break.
+pub async fn toggle_user_break(data: &Data, user_id: UserId) -> Option<bool> {
+    let study_states = data.study_states.lock().await;
+    let state = study_states.get(&user_id)?;
+
+    let on_break = state.toggle_break(OffsetDateTime::now_utc()).await;
+    state.persist(&ActOnUser(&data.db_pool, user_id)).await;
+
+    Some(on_break)
+}
+
+/// Content and toggle button for a message showing the user's break state.
+pub fn break_message(on_break: bool) -> (String, CreateButton) {
+    if on_break {
+        (
+            ":coffee: You are on a break. Break time does not earn coins.".to_string(),
+            CreateButton::new("toggle_break")
+                .label("Resume studying")
+                .style(ButtonStyle::Success)
+        )
+    } else {
+        (
+            ":books: You are studying.".to_string(),
+            CreateButton::new("toggle_break")
+                .label("Take a break")
+                .style(ButtonStyle::Secondary)
+        )
     }
 }
 
@@ -0,0 +1,80 @@
+use poise::serenity_prelude::{CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption};
+use sqlx::SqlitePool;
+
+use crate::prelude::ActOnUser;
+
+/// Longest allowed subject name.
+pub const MAX_SUBJECT_LENGTH: usize = 50;
+
+/// Select menu value for adding a subject that has not been used before.
+pub const NEW_SUBJECT_VALUE: &str = "new";
+
+/// Subjects the user has tagged sessions with, most recently used first.
+pub async fn user_subjects(ctx: &ActOnUser<'_>) -> Vec<String> {
+    let uid = ctx.uid();
+
+    sqlx::query!("
+    SELECT subject
+    FROM session_tags
+    JOIN study_sessions ON session_id = study_sessions.id
+    JOIN users ON user_id = users.id
+    WHERE uid = $1
+    GROUP BY subject
+    ORDER BY MAX(session_id) DESC
+    LIMIT 24
+    ", uid)
+        .fetch_all(ctx.0)
+        .await.unwrap()
+        .into_iter()
+        .map(|r| r.subject)
+        .collect()
+}
+
+pub async fn user_subject_preset(ctx: &ActOnUser<'_>) -> Option<String> {
+    let uid = ctx.uid();
+
+    sqlx::query!("
+    SELECT subject FROM subject_presets
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ", uid)
+        .fetch_optional(ctx.0)
+        .await.unwrap()
+        .map(|r| r.subject)
+}
+
+pub async fn session_subject(pool: &SqlitePool, session_id: i64) -> Option<String> {
+    sqlx::query!("SELECT subject FROM session_tags WHERE session_id = $1", session_id)
+        .fetch_optional(pool)
+        .await.unwrap()
+        .map(|r| r.subject)
+}
+
+pub async fn tag_session(pool: &SqlitePool, session_id: i64, subject: &str) {
+    sqlx::query!("
+    INSERT OR REPLACE INTO session_tags (session_id, subject)
+    VALUES ($1, $2)
+    ", session_id, subject)
+        .execute(pool)
+        .await.unwrap();
+}
+
+/// Menu for choosing the subject of a session, among the user's earlier subjects or a new one.
+pub fn subject_menu(session_id: i64, subjects: &[String], current: Option<&str>) -> CreateSelectMenu {
+    let mut options = subjects
+        .iter()
+        .map(|s| CreateSelectMenuOption::new(s, format!("subject:{}", s))
+            .default_selection(current == Some(s.as_str())))
+        .collect::<Vec<_>>();
+
+    if let Some(current) = current.filter(|c| !subjects.iter().any(|s| s == c)) {
+        options.insert(0, CreateSelectMenuOption::new(current, format!("subject:{}", current))
+            .default_selection(true));
+        options.truncate(24);
+    }
+
+    options.push(CreateSelectMenuOption::new("New subject...", NEW_SUBJECT_VALUE)
+        .emoji('➕'));
+
+    CreateSelectMenu::new(format!("tag_session_{}", session_id), CreateSelectMenuKind::String { options })
+        .placeholder("Subject")
+}
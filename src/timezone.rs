@@ -0,0 +1,82 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use sqlx::types::time::OffsetDateTime;
+use time::{Date, Month};
+
+use crate::prelude::ActOnUser;
+
+/// Timezone the user has set, UTC if none.
+pub async fn user_timezone(ctx: &ActOnUser<'_>) -> Tz {
+    let uid = ctx.uid();
+
+    sqlx::query!("
+    SELECT timezone FROM user_timezones
+    WHERE user_id IN (SELECT id FROM users WHERE uid = $1)
+    ", uid)
+        .fetch_optional(ctx.0)
+        .await.unwrap()
+        .and_then(|r| r.timezone.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+pub fn from_naive(date: NaiveDate) -> Date {
+    Date::from_calendar_date(
+        date.year(),
+        Month::try_from(date.month() as u8).unwrap(),
+        date.day() as u8).unwrap()
+}
+
+fn to_naive(date: Date) -> NaiveDate {
+    NaiveDate::from_ymd_opt(date.year(), date.month() as u32, date.day() as u32).unwrap()
+}
+
+/// The day it is in a timezone at some point in time.
+pub fn local_date(at: OffsetDateTime, tz: Tz) -> Date {
+    from_naive(tz.timestamp_opt(at.unix_timestamp(), 0).unwrap().date_naive())
+}
+
+/// Start of a day in a timezone.
+pub fn local_midnight(date: Date, tz: Tz) -> OffsetDateTime {
+    let date = to_naive(date);
+
+    // Some timezones skip midnight when switching to daylight saving time.
+    let start = (0..24)
+        .find_map(|hour| tz.from_local_datetime(&date.and_hms_opt(hour, 0, 0).unwrap()).earliest())
+        .unwrap();
+
+    OffsetDateTime::from_unix_timestamp(start.timestamp()).unwrap()
+}
+
+/// Point in time of a local date and time in a timezone, if it exists there.
+pub fn from_local(at: NaiveDateTime, tz: Tz) -> Option<OffsetDateTime> {
+    tz.from_local_datetime(&at)
+        .earliest()
+        .and_then(|at| OffsetDateTime::from_unix_timestamp(at.timestamp()).ok())
+}
+
+/// Days in a timezone that the time between start and end spans,
+/// with the fraction of the time that falls on each day.
+pub fn split_across_days(start: OffsetDateTime, end: OffsetDateTime, tz: Tz) -> Vec<(Date, f64)> {
+    let total = (end - start).as_seconds_f64();
+    let last_day = local_date(end, tz);
+
+    if total <= 0.0 {
+        return vec![(last_day, 1.0)]
+    }
+
+    let mut day = local_date(start, tz);
+    let mut parts = Vec::new();
+
+    while day <= last_day {
+        let next_day = day.next_day().unwrap();
+        let part = local_midnight(next_day, tz).min(end) - local_midnight(day, tz).max(start);
+
+        if part.is_positive() {
+            parts.push((day, part.as_seconds_f64() / total));
+        }
+
+        day = next_day;
+    }
+
+    parts
+}
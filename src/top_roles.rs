@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use log::{error, info};
+use poise::serenity_prelude::{CacheHttp, Context, GuildId, RoleId, UserId};
+use sqlx::SqlitePool;
+
+use crate::{leaderboard::{fetch_leaderboard, LeaderboardMetric, LeaderboardPeriod}, Config, Error};
+
+/// Gives the configured top rank roles to the current top of the monthly leaderboard,
+/// and takes them from everyone that is no longer there.
+pub async fn sync_top_roles(ctx: &Context, pool: &SqlitePool, config: &Config) -> Result<(), Error> {
+    let Some(top_roles) = &config.top_roles else { return Ok(()) };
+    let guild_id = GuildId::new(top_roles.guild);
+    let roles = &top_roles.roles;
+
+    let Some(max_places) = roles.iter().map(|r| r.places).max() else { return Ok(()) };
+
+    let (start, end) = LeaderboardPeriod::Month.bounds();
+    let top = fetch_leaderboard(pool, &config.study_rules, LeaderboardMetric::StudyTime, start, end, Some(max_places as i16), 0).await;
+
+    for role in roles {
+        let role_id = RoleId::new(role.role);
+        let rid = role.role as i64;
+
+        let holders = sqlx::query!("
+        SELECT uid FROM top_role_holders
+        JOIN users ON user_id = users.id
+        WHERE role_id = $1
+        ", rid)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|r| UserId::new(r.uid as u64))
+            .collect::<HashSet<_>>();
+
+        let deserving = top
+            .iter()
+            .take(role.places as usize)
+            .map(|(user_id, _)| *user_id)
+            .collect::<HashSet<_>>();
+
+        for user_id in holders.difference(&deserving) {
+            info!("Removing top role {} from {}", role_id, user_id);
+
+            // The member may have left the guild, so the role is forgotten either way.
+            let _ = ctx.http().remove_member_role(guild_id, *user_id, role_id, Some("No longer in the top of the leaderboard")).await;
+
+            let uid = i64::from(*user_id);
+            sqlx::query!("
+            DELETE FROM top_role_holders
+            WHERE role_id = $1 AND user_id IN (SELECT id FROM users WHERE uid = $2)
+            ", rid, uid)
+                .execute(pool)
+                .await?;
+        }
+
+        for user_id in deserving.difference(&holders) {
+            info!("Giving top role {} to {}", role_id, user_id);
+
+            // The member may have left the guild, which should not keep the others from getting it.
+            // Not saved as a holder, so it is tried again on the next sync.
+            if let Err(e) = ctx.http().add_member_role(guild_id, *user_id, role_id, Some("Reached the top of the leaderboard")).await {
+                error!("Failed to give top role {} to {}: {:?}", role_id, user_id, e);
+                continue
+            }
+
+            let uid = i64::from(*user_id);
+            sqlx::query!("
+            INSERT OR IGNORE INTO top_role_holders (role_id, user_id)
+            SELECT $1, id FROM users WHERE uid = $2
+            ", rid, uid)
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    Ok(())
+}